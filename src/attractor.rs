@@ -5,6 +5,10 @@ use glam::Vec3;
 pub struct Attractor {
     pub pos: Vec3,
     pub reached: bool,
+    /// Scales how hard this attractor pulls the closest branch.
+    pub weight: f32,
+    /// Index into the generator's `attractor_sets`.
+    pub set: usize,
 }
 
 impl Attractor {
@@ -12,6 +16,8 @@ impl Attractor {
         Self {
             pos,
             reached: false,
+            weight: 1.0,
+            set: 0,
         }
     }
 }
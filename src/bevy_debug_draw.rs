@@ -10,7 +10,7 @@ use glam::Vec3 as ShrubVec3;
 
 use crate::{
     bevy_plugin::ShrubberyAsset,
-    shape::AttractorShape,
+    filter::FilterContext,
     shrubbery::{ShrubberyGenerator, ShrubberySettings, ShrubberyStep, SpawnAttractorLocation},
 };
 
@@ -115,11 +115,11 @@ struct AttractorVolumeDebug {
 /// A non-root branch segment, colored by the step that produced it.
 ///
 /// Stores both endpoints rather than pos+dir: the segment runs from the
-/// parent's position to the branch's position, and `dir` only describes where
+/// branch's start position to its position, and `dir` only describes where
 /// the branch *head* points next (and may be bent by later growth steps).
 #[derive(Clone, Copy, Debug)]
 struct BranchDebug {
-    /// Position of the parent branch (segment start).
+    /// Start of the segment, usually the parent branch's position.
     start: Vec3,
     /// Position of this branch (segment end / tip).
     end: Vec3,
@@ -152,8 +152,11 @@ fn step_name(step: &ShrubberyStep) -> &'static str {
         ShrubberyStep::SpawnRoot(_) => "SpawnRootBranch",
         ShrubberyStep::Grow(_) => "GrowDirection",
         ShrubberyStep::SpawnAttractors(_) => "SpawnAttractors",
+        ShrubberyStep::RemoveAttractors(_) => "RemoveAttractors",
         ShrubberyStep::ClearAttractors => "ClearAttractors",
+        ShrubberyStep::ClearAttractorSet(_) => "ClearAttractorSet",
         ShrubberyStep::Shape(_) => "SpawnLeaves",
+        ShrubberyStep::ComputeThickness(_) => "ComputeThickness",
     }
 }
 
@@ -167,6 +170,7 @@ fn compute_debug_cache(seed: u64, settings: &ShrubberySettings) -> ShrubberyDebu
     for (step_index, step) in settings.build_steps.iter().enumerate() {
         let branch_start = generator.branches.len();
         let attractor_start = generator.attractors.len();
+        let rng_before_step = generator.rng.clone();
         generator.execute_step(step);
         branch_steps.extend((branch_start..generator.branches.len()).map(|i| (i, step_index)));
         match step {
@@ -181,15 +185,14 @@ fn compute_debug_cache(seed: u64, settings: &ShrubberySettings) -> ShrubberyDebu
                     }));
             }
             ShrubberyStep::SpawnAttractors(params) => {
-                let half_extents = match &params.shape {
-                    AttractorShape::Cube(cube) => {
-                        Vec3::new(cube.size_x, cube.size_y, cube.size_z) * 0.5
-                    }
-                };
+                // volumes are drawn as the shape's bounding box
+                let (bounds_min, bounds_max) = params.shape.bounds();
+                let bounds_center = (bounds_min + bounds_max) * 0.5;
+                let half_extents = to_bevy_vec3((bounds_max - bounds_min) * 0.5);
                 match &params.location {
                     SpawnAttractorLocation::Pos(pos) => {
                         cache.attractor_volumes.push(AttractorVolumeDebug {
-                            center: to_bevy_vec3(*pos),
+                            center: to_bevy_vec3(*pos + bounds_center),
                             half_extents,
                             attractors: generator.attractors[attractor_start..]
                                 .iter()
@@ -207,39 +210,38 @@ fn compute_debug_cache(seed: u64, settings: &ShrubberySettings) -> ShrubberyDebu
                         // `ShrubberyGenerator::spawn_attractors_on_branches`. The step
                         // only appends attractors, so branch state is unchanged and
                         // the origins can be selected after execution.
+                        let filter_context = FilterContext {
+                            step_index,
+                            ..generator.filter_context()
+                        };
                         let centers: Vec<ShrubVec3> = generator
                             .branches
                             .iter()
-                            .filter(|branch| branch.parent_index.is_some())
-                            .filter(|branch| {
+                            .enumerate()
+                            .filter(|(_, branch)| branch.parent_index.is_some())
+                            .filter(|(i, _)| {
                                 from_branch
                                     .filter
-                                    .should_include_branch(branch, generator.last_known_id)
+                                    .should_include_branch(*i, &filter_context)
+                            })
+                            .map(|(_, branch)| {
+                                branch.pos + from_branch.offset.offset_dir(branch.dir)
                             })
-                            .map(|branch| branch.pos + from_branch.offset.offset_dir(branch.dir))
                             .collect();
-                        let half_extents = match &params.shape {
-                            AttractorShape::Cube(cube) => {
-                                Vec3::new(cube.size_x, cube.size_y, cube.size_z) * 0.5
+                        // attractors are appended one volume at a time, in branch order,
+                        // shapes reject cells so replay each volume from the step's rng
+                        let mut rng = rng_before_step;
+                        let volumes = generator.branch_volumes();
+                        for center in centers {
+                            let mut attractors = Vec::new();
+                            params.generate(center, &mut attractors, &mut rng);
+                            if params.reject_inside_branches {
+                                attractors.retain(|a| !volumes.contains(a.pos));
                             }
-                        };
-                        // attractors are appended one volume at a time, in branch order
-                        let per_volume: usize = match &params.shape {
-                            AttractorShape::Cube(cube) => {
-                                let spacing = params.attractor_spacing.attractor_spacing.max(0.001);
-                                [cube.size_x, cube.size_y, cube.size_z]
-                                    .map(|v| (v / spacing).ceil() as usize)
-                                    .iter()
-                                    .product()
-                            }
-                        };
-                        for (i, center) in centers.into_iter().enumerate() {
-                            let start = (i * per_volume).min(new_attractors.len());
-                            let end = ((i + 1) * per_volume).min(new_attractors.len());
                             cache.attractor_volumes.push(AttractorVolumeDebug {
-                                center: to_bevy_vec3(center),
+                                center: to_bevy_vec3(center + bounds_center),
                                 half_extents,
-                                attractors: new_attractors[start..end]
+                                attractors: attractors
                                     .iter()
                                     .map(|attractor| to_bevy_vec3(attractor.pos))
                                     .collect(),
@@ -254,11 +256,11 @@ fn compute_debug_cache(seed: u64, settings: &ShrubberySettings) -> ShrubberyDebu
     for (index, step_index) in branch_steps {
         let branch = &generator.branches[index];
         // roots are covered by the root branch markers
-        let Some(parent) = branch.parent_index else {
+        if branch.parent_index.is_none() {
             continue;
-        };
+        }
         cache.branches.push(BranchDebug {
-            start: to_bevy_vec3(generator.branches[parent].pos),
+            start: to_bevy_vec3(branch.start_pos),
             end: to_bevy_vec3(branch.pos),
             step_index,
         });
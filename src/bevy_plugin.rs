@@ -1,18 +1,25 @@
 //! Bevy bridge, to make integrating Shrubbery easier via [`ShrubberyAsset`]
 //! and auto resolving the [`ShrubberyAsset`]'s voxel ids
 //! utilizing [`VoxelDefinitions`] as a Resource
+//! [`ShrubberyTree`] entities are generated and meshed automatically
 use std::ops::Deref;
 
-use ahash::HashSet;
+use ahash::{HashMap, HashSet};
 use bevy::{
-    asset::{Asset, AssetLoader},
+    asset::{Asset, AssetLoader, RenderAssetUsages},
+    mesh::{Indices, PrimitiveTopology},
     prelude::*,
     reflect::TypePath,
+    tasks::{AsyncComputeTaskPool, Task, block_on, futures_lite::future},
 };
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
-use crate::{shrubbery::ShrubberySettings, voxel::VoxelDefinitions};
+use crate::{
+    mesh::VoxelMesh,
+    shrubbery::{ShrubberyGenerator, ShrubberySettings},
+    voxel::{VoxelDefinitions, VoxelId},
+};
 
 /// Bevy bridge, to load [`ShrubberyAsset`] and auto resolve their voxel ids
 /// utilizing [`VoxelDefinitions`] as a Resource
@@ -23,12 +30,16 @@ impl Plugin for ShrubberyPlugin {
         app.init_asset::<ShrubberyAsset>();
         app.init_asset_loader::<ShrubberyAssetLoader>();
         app.insert_resource(TreeAssetsAwaitingSync(HashSet::default()));
+        // tree tasks start after voxel ids resolve, so they never mesh stale ids
         app.add_systems(
             PostUpdate,
             (
                 begin_sync_voxel_ids_with_tree_assets,
                 sync_voxel_ids_with_tree_assets,
-            ),
+                spawn_shrubbery_tree_tasks,
+                apply_shrubbery_tree_tasks,
+            )
+                .chain(),
         );
     }
 }
@@ -65,11 +76,14 @@ impl AssetLoader for ShrubberyAssetLoader {
         &self,
         reader: &mut dyn bevy::asset::io::Reader,
         _settings: &Self::Settings,
-        _load_context: &mut bevy::asset::LoadContext<'_>,
+        load_context: &mut bevy::asset::LoadContext<'_>,
     ) -> Result<Self::Asset, Self::Error> {
         let mut bytes = Vec::new();
         reader.read_to_end(&mut bytes).await?;
         let asset: Self::Asset = ron::de::from_bytes(&bytes)?;
+        for diagnostic in asset.validate() {
+            warn!("{}: {diagnostic}", load_context.path());
+        }
         Ok(asset)
     }
 
@@ -114,6 +128,18 @@ fn sync_voxel_ids_with_tree_assets(
     mut tree_assets: ResMut<Assets<ShrubberyAsset>>,
     voxel_definitions: Option<Res<VoxelDefinitions>>,
 ) {
+    // a replaced or edited registry invalidates every resolved id
+    if let Some(voxel_definitions) = &voxel_definitions
+        && voxel_definitions.is_changed()
+        && !voxel_definitions.is_added()
+    {
+        let ids: Vec<_> = tree_assets.ids().collect();
+        for id in ids {
+            if let Some(asset) = tree_assets.get_mut_untracked(id) {
+                asset.0.resolve_voxel_definitions(voxel_definitions);
+            }
+        }
+    }
     awaiting.0.retain(|id| {
         let Some(asset) = tree_assets.get_mut_untracked(*id) else {
             return false; // id no longer valid
@@ -125,3 +151,158 @@ fn sync_voxel_ids_with_tree_assets(
         false
     });
 }
+
+/// Generates, meshes and displays a [`ShrubberyAsset`].
+///
+/// Generation runs as a [`ShrubberyTreeTask`] in the background. Once finished
+/// the mesh is attached as a [`ShrubberyTreeMesh`] child. It is rebuilt whenever
+/// this component changes, the asset is (re)loaded or [`VoxelDefinitions`]
+/// / [`VoxelColors`] change.
+#[derive(Component, Clone, Debug)]
+#[require(Transform, Visibility)]
+pub struct ShrubberyTree {
+    pub asset: Handle<ShrubberyAsset>,
+    pub seed: u64,
+}
+
+/// Marks the child entity holding the generated mesh of a [`ShrubberyTree`].
+#[derive(Component, Debug)]
+pub struct ShrubberyTreeMesh;
+
+/// Vertex colors for [`ShrubberyTree`] meshes, voxel ids missing here are white.
+#[derive(Resource, Clone, Default, Debug)]
+pub struct VoxelColors(pub HashMap<VoxelId, Color>);
+
+/// Material used by every [`ShrubberyTreeMesh`]. Inserted on first use with a
+/// default [`StandardMaterial`] unless the app provides its own.
+#[derive(Resource, Clone, Debug)]
+pub struct ShrubberyTreeMaterial(pub Handle<StandardMaterial>);
+
+/// Convert a [`VoxelMesh`] into a bevy [`Mesh`], with vertex colors when `colors` are given.
+pub fn voxel_mesh_to_bevy(voxel_mesh: &VoxelMesh, colors: Option<&VoxelColors>) -> Mesh {
+    let mut mesh = Mesh::new(
+        PrimitiveTopology::TriangleList,
+        RenderAssetUsages::default(),
+    )
+    .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, voxel_mesh.positions.clone())
+    .with_inserted_attribute(Mesh::ATTRIBUTE_NORMAL, voxel_mesh.normals.clone())
+    .with_inserted_attribute(Mesh::ATTRIBUTE_UV_0, voxel_mesh.uvs.clone())
+    .with_inserted_indices(Indices::U32(voxel_mesh.indices.clone()));
+    if let Some(colors) = colors {
+        let vertex_colors: Vec<[f32; 4]> = voxel_mesh
+            .vertex_voxels()
+            .map(|voxel_id| {
+                colors
+                    .0
+                    .get(&voxel_id)
+                    .copied()
+                    .unwrap_or(Color::WHITE)
+                    .to_linear()
+                    .to_f32_array()
+            })
+            .collect();
+        mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, vertex_colors);
+    }
+    mesh
+}
+
+/// In flight generation of a [`ShrubberyTree`] on the [`AsyncComputeTaskPool`].
+///
+/// Replacing or dropping this component (e.g. despawning the entity) cancels
+/// the task, so a stale result is never applied.
+#[derive(Component)]
+pub struct ShrubberyTreeTask(Task<Option<Mesh>>);
+
+fn spawn_shrubbery_tree_tasks(
+    mut commands: Commands,
+    mut events: MessageReader<AssetEvent<ShrubberyAsset>>,
+    tree_assets: Res<Assets<ShrubberyAsset>>,
+    voxel_definitions: Option<Res<VoxelDefinitions>>,
+    voxel_colors: Option<Res<VoxelColors>>,
+    trees: Query<(Entity, Ref<ShrubberyTree>)>,
+) {
+    let mut asset_updates = HashSet::default();
+    for event in events.read() {
+        if let AssetEvent::LoadedWithDependencies { id } | AssetEvent::Modified { id } = event {
+            asset_updates.insert(*id);
+        }
+    }
+    let rebuild_all = voxel_definitions.is_some_and(|d| d.is_changed())
+        || voxel_colors.as_ref().is_some_and(|c| c.is_changed());
+
+    let task_pool = AsyncComputeTaskPool::get();
+    for (entity, tree) in &trees {
+        if !rebuild_all && !tree.is_changed() && !asset_updates.contains(&tree.asset.id()) {
+            continue;
+        }
+        let Some(asset) = tree_assets.get(&tree.asset) else {
+            // not loaded yet, rebuilt by the `LoadedWithDependencies` event
+            continue;
+        };
+        let settings = asset.0.clone();
+        let seed = tree.seed;
+        let voxel_colors = voxel_colors.as_deref().cloned();
+        let task = task_pool.spawn(async move {
+            let grid = match ShrubberyGenerator::generate(seed, &settings).voxelize_grid() {
+                Ok(grid) => grid,
+                Err(err) => {
+                    error!("shrubbery tree with seed {seed}: {err}");
+                    return None;
+                }
+            };
+            let voxel_mesh = grid.greedy_mesh();
+            (!voxel_mesh.is_empty()).then(|| voxel_mesh_to_bevy(&voxel_mesh, voxel_colors.as_ref()))
+        });
+        // replaces (and cancels) any task still running for an older seed or asset
+        commands.entity(entity).insert(ShrubberyTreeTask(task));
+    }
+}
+
+fn apply_shrubbery_tree_tasks(
+    mut commands: Commands,
+    tree_material: Option<Res<ShrubberyTreeMaterial>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut tasks: Query<(Entity, &mut ShrubberyTreeTask, Option<&Children>)>,
+    tree_meshes: Query<(), With<ShrubberyTreeMesh>>,
+) {
+    let mut material = tree_material.map(|m| m.0.clone());
+    for (entity, mut task, children) in &mut tasks {
+        let Some(mesh) = block_on(future::poll_once(&mut task.0)) else {
+            continue;
+        };
+        commands.entity(entity).remove::<ShrubberyTreeTask>();
+
+        let existing_mesh_entity = children
+            .into_iter()
+            .flat_map(|children| children.iter())
+            .find(|child| tree_meshes.contains(*child));
+        let Some(mesh) = mesh else {
+            if let Some(mesh_entity) = existing_mesh_entity {
+                commands.entity(mesh_entity).despawn();
+            }
+            continue;
+        };
+        let mesh = Mesh3d(meshes.add(mesh));
+        match existing_mesh_entity {
+            Some(mesh_entity) => {
+                commands.entity(mesh_entity).insert(mesh);
+            }
+            None => {
+                let material = material
+                    .get_or_insert_with(|| {
+                        let handle = materials.add(StandardMaterial::default());
+                        commands.insert_resource(ShrubberyTreeMaterial(handle.clone()));
+                        handle
+                    })
+                    .clone();
+                commands.spawn((
+                    ShrubberyTreeMesh,
+                    mesh,
+                    MeshMaterial3d(material),
+                    ChildOf(entity),
+                ));
+            }
+        }
+    }
+}
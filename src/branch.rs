@@ -4,14 +4,22 @@ use glam::Vec3;
 
 use crate::shrubbery::BranchGrowthDirection;
 
+/// How much wider than its end a sprouted segment may start, per unit of length.
+const MAX_SPROUT_FLARE: f32 = 0.5;
+
 /// A single segment of the generated tree.
 #[derive(Debug)]
 pub struct Branch {
     pub pos: Vec3,
+    /// Where the segment starts. The parent's position, unless the branch
+    /// sprouts part way along the parent's segment.
+    pub start_pos: Vec3,
     pub parent_index: Option<usize>,
     pub dir: Vec3,
-    /// Thickness radius used when rasterizing bark.
+    /// Thickness radius used when rasterizing bark, at this end of the segment.
     pub thickness: f32,
+    /// Thickness radius at [`Self::start_pos`], the bark tapers to [`Self::thickness`].
+    pub start_thickness: f32,
     /// Direction before attractors pulled it; restored by [`reset`](Self::reset).
     pub original_dir: Vec3,
     /// How many attractors are currently pulling this node.
@@ -19,6 +27,8 @@ pub struct Branch {
     pub child_count: i32,
     /// Generation id, used for filtering.
     pub id: u32,
+    /// Bit mask of the [`BranchTag`](crate::tag::BranchTag)s assigned by the creating step.
+    pub tags: u64,
     pub iteration: u32,
     pub iteration_total: u32,
     /// Index into the generator's `leaf_groups`, set by a `SpawnLeaves` step.
@@ -45,18 +55,31 @@ impl Branch {
             BranchGrowthDirection::GravityLean { strength } => {
                 (self.dir + Vec3::NEG_Y * *strength).normalize()
             }
+            BranchGrowthDirection::SteerToward { dir, max_degrees } => match dir.try_normalize() {
+                Some(target) => self
+                    .dir
+                    .rotate_towards(target, max_degrees.max(0.0).to_radians())
+                    .normalize(),
+                None => self.dir,
+            },
+            BranchGrowthDirection::Tropism(tropism) => {
+                tropism.apply(self.dir, iteration, iteration_total)
+            }
             BranchGrowthDirection::Target(dir) => *dir,
             BranchGrowthDirection::WorldPos(world_pos) => {
                 let to_world = world_pos - self.pos;
                 branch_len = to_world.length();
                 to_world.normalize()
             }
+            // resolved to a Target by the generator, which owns the seed
+            BranchGrowthDirection::Wander { .. } => self.dir,
             // should technically never be set here, but self.dir, shoul be set from attractors
             BranchGrowthDirection::Attractor { .. } => self.dir,
         };
 
         Self {
             pos: self.pos + dir * branch_len,
+            start_pos: self.pos,
             parent_index: Some(index),
             dir,
             attractors_count: 0,
@@ -64,13 +87,22 @@ impl Branch {
             child_count: 0,
             leaf_group: None,
             thickness,
+            start_thickness: thickness,
             decoration_group: None,
             iteration,
             iteration_total,
             id,
+            tags: 0,
         }
     }
 
+    /// Start the bark at the host's `radius` where the segment sprouts, capped so a
+    /// short shoot on a thick host flares out instead of rasterizing as a ball.
+    pub fn sprout_from(&mut self, radius: f32) {
+        let flare = self.pos.distance(self.start_pos) * MAX_SPROUT_FLARE;
+        self.start_thickness = radius.min(self.thickness + flare);
+    }
+
     pub fn reset(&mut self) {
         self.attractors_count = 0;
         self.dir = self.original_dir;
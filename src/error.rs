@@ -0,0 +1,42 @@
+//! errors reported while turning a generated shrubbery into voxels
+use glam::UVec3;
+use thiserror::Error;
+
+/// Why [`ShrubberyGenerator::try_voxelize`](crate::shrubbery::ShrubberyGenerator::try_voxelize) failed.
+/// `step_index` indexes [`ShrubberySettings::build_steps`](crate::shrubbery::ShrubberySettings::build_steps).
+#[derive(Clone, Debug, PartialEq, Eq, Error)]
+pub enum ShrubberyError {
+    /// A `Random` or `RandomWeighted` voxel selector of a grow step had nothing to pick.
+    #[error(
+        "build_steps[{step_index}]: branch {branch_index}: voxel selector is empty or has no positive weight"
+    )]
+    EmptyBranchDecoration {
+        step_index: usize,
+        branch_index: usize,
+    },
+    /// A `Random` or `RandomWeighted` voxel selector of a shape step had nothing to pick.
+    #[error("build_steps[{step_index}]: shape voxel selector is empty or has no positive weight")]
+    EmptyShapeDecoration { step_index: usize },
+    /// A non-root branch that wasn't grown by a grow step.
+    #[error("branch {branch_index} has no decoration group")]
+    MissingDecorationGroup { branch_index: usize },
+    #[error("branch {branch_index} references decoration group {group}, which does not exist")]
+    UnknownDecorationGroup { branch_index: usize, group: usize },
+    #[error("branch {branch_index} references parent {parent_index}, which does not exist")]
+    UnknownParent {
+        branch_index: usize,
+        parent_index: usize,
+    },
+}
+
+/// Why a [`VoxelGrid`](crate::voxel_grid::VoxelGrid) could not be built or deserialized.
+#[derive(Clone, Debug, PartialEq, Eq, Error)]
+pub enum VoxelGridError {
+    /// The cell count of `size` exceeds [`MAX_CELLS`](crate::voxel_grid::MAX_CELLS).
+    #[error("a grid of size {size} has more than {max} cells", max = crate::voxel_grid::MAX_CELLS)]
+    TooLarge { size: UVec3 },
+    #[error("grid of {expected} cells stores {found}")]
+    CellCountMismatch { expected: usize, found: usize },
+    #[error("cell {cell} points past the palette of {palette_len} voxel ids")]
+    PaletteIndexOutOfRange { cell: u32, palette_len: usize },
+}
@@ -1,7 +1,12 @@
+use std::cell::OnceCell;
+
+use glam::Vec3;
+use rand::{RngExt, SeedableRng};
+use rand_chacha::ChaCha8Rng;
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
-use crate::branch::Branch;
+use crate::{branch::Branch, tag::BranchTag};
 
 /// Decides what branches to operate on based upon different filtering criteria
 #[derive(Clone, Debug)]
@@ -15,6 +20,8 @@ pub struct Filter {
     pub ignore_root: bool,
     pub id: IdFilter,
     pub iteration: IterationFilter,
+    /// Extra conditions on top of the fields above, matches everything by default.
+    pub expr: FilterExpr,
 }
 
 impl Default for Filter {
@@ -24,19 +31,21 @@ impl Default for Filter {
             ignore_root: true,
             id: IdFilter::default(),
             iteration: IterationFilter::default(),
+            expr: FilterExpr::default(),
         }
     }
 }
 
 impl Filter {
-    pub fn should_include_branch(&self, branch: &Branch, last_id: u32) -> bool {
+    pub fn should_include_branch(&self, branch_index: usize, context: &FilterContext) -> bool {
+        let branch = &context.branches[branch_index];
         if self.ignore_shapes && branch.leaf_group.is_some() {
             return false;
         }
         if self.ignore_root && branch.parent_index.is_none() {
             return false;
         }
-        if !self.id.is_id_included(branch, last_id) {
+        if !self.id.is_id_included(branch, context.last_id) {
             return false;
         }
         if !self
@@ -45,12 +54,172 @@ impl Filter {
         {
             return false;
         }
-        true
+        self.expr.matches(branch_index, context)
+    }
+}
+
+/// The state a [`Filter`] is evaluated against
+pub struct FilterContext<'a> {
+    pub branches: &'a [Branch],
+    /// Id assigned by the most recent step, used by [`IdFilter::Last`]
+    pub last_id: u32,
+    /// Generator seed, [`FilterExpr::Chance`] derives its per-branch rolls from it
+    pub seed: u64,
+    /// Index of the step evaluating the filter, so each step rolls differently
+    pub step_index: usize,
+    /// (depth, root index) of every branch, built on first use by
+    /// [`FilterExpr::Depth`] or [`FilterExpr::RootDistance`]
+    pub(crate) lineage: OnceCell<Vec<(u32, usize)>>,
+}
+
+impl FilterContext<'_> {
+    /// Depth and root index of `branch_index`, computed for every branch in one pass.
+    fn lineage(&self, branch_index: usize) -> (u32, usize) {
+        let lineage = self.lineage.get_or_init(|| {
+            let mut lineage: Vec<(u32, usize)> = Vec::with_capacity(self.branches.len());
+            for (index, branch) in self.branches.iter().enumerate() {
+                // growth appends after the parent, so its entry is already known
+                let entry = match branch.parent_index {
+                    Some(parent) if parent < index => {
+                        let (depth, root) = lineage[parent];
+                        (depth + 1, root)
+                    }
+                    _ => (0, index),
+                };
+                lineage.push(entry);
+            }
+            lineage
+        });
+        lineage[branch_index]
+    }
+}
+
+/// Inclusive `min..=max` used by [`FilterExpr`], missing bounds are unbounded
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(default))]
+pub struct FilterRangeF32 {
+    pub min: f32,
+    pub max: f32,
+}
+
+impl Default for FilterRangeF32 {
+    fn default() -> Self {
+        Self {
+            min: f32::NEG_INFINITY,
+            max: f32::INFINITY,
+        }
+    }
+}
+
+impl FilterRangeF32 {
+    pub fn contains(&self, value: f32) -> bool {
+        self.min <= value && value <= self.max
+    }
+}
+
+/// Inclusive `min..=max` used by [`FilterExpr`], missing bounds are unbounded
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(default))]
+pub struct FilterRangeU32 {
+    pub min: u32,
+    pub max: u32,
+}
+
+impl Default for FilterRangeU32 {
+    fn default() -> Self {
+        Self {
+            min: 0,
+            max: u32::MAX,
+        }
+    }
+}
+
+impl FilterRangeU32 {
+    pub fn contains(&self, value: u32) -> bool {
+        self.min <= value && value <= self.max
+    }
+}
+
+/// Composable branch condition, used by [`Filter::expr`]
+/// ```ron
+/// // id 2 or 3, below height 10, without children
+/// expr: All([Any([Id(Target(2)), Id(Target(3))]), Height((max: 10.0)), ChildCount((max: 0))])
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum FilterExpr {
+    /// Every inner expression matches, true when empty
+    All(Vec<FilterExpr>),
+    /// At least one inner expression matches, false when empty
+    Any(Vec<FilterExpr>),
+    Not(Box<FilterExpr>),
+    Id(IdFilter),
+    Iteration(IterationFilter),
+    /// World Y of the branch end point
+    Height(FilterRangeF32),
+    /// Number of segments between the branch and its root, roots are 0
+    Depth(FilterRangeU32),
+    /// Branches grown directly from this one
+    ChildCount(FilterRangeU32),
+    /// Branch direction is within `max_degrees` of `dir`
+    DirectionCone {
+        dir: Vec3,
+        max_degrees: f32,
+    },
+    Thickness(FilterRangeF32),
+    /// Keep each branch with this percent chance (0-100), rolled once per branch and step
+    Chance(f32),
+    /// Straight line distance from the branch end point to its root
+    RootDistance(FilterRangeF32),
+}
+
+impl Default for FilterExpr {
+    fn default() -> Self {
+        Self::All(Vec::new())
+    }
+}
+
+impl FilterExpr {
+    pub fn matches(&self, branch_index: usize, context: &FilterContext) -> bool {
+        let Some(branch) = context.branches.get(branch_index) else {
+            return false;
+        };
+        match self {
+            FilterExpr::All(exprs) => exprs.iter().all(|e| e.matches(branch_index, context)),
+            FilterExpr::Any(exprs) => exprs.iter().any(|e| e.matches(branch_index, context)),
+            FilterExpr::Not(expr) => !expr.matches(branch_index, context),
+            FilterExpr::Id(id) => id.is_id_included(branch, context.last_id),
+            FilterExpr::Iteration(iteration) => {
+                iteration.is_iteration_included(branch.iteration, branch.iteration_total)
+            }
+            FilterExpr::Height(range) => range.contains(branch.pos.y),
+            FilterExpr::Depth(range) => range.contains(context.lineage(branch_index).0),
+            FilterExpr::ChildCount(range) => {
+                range.contains(u32::try_from(branch.child_count).unwrap_or_default())
+            }
+            FilterExpr::DirectionCone { dir, max_degrees } => {
+                let cos_angle = branch.dir.normalize_or_zero().dot(dir.normalize_or_zero());
+                cos_angle >= max_degrees.to_radians().cos()
+            }
+            FilterExpr::Thickness(range) => range.contains(branch.thickness),
+            FilterExpr::Chance(percent) => {
+                // a fixed stream per step and word offset per branch, so the roll
+                // doesn't depend on evaluation order or the generator's rng
+                let mut rng = ChaCha8Rng::seed_from_u64(context.seed);
+                rng.set_stream(context.step_index as u64);
+                rng.set_word_pos(branch_index as u128);
+                rng.random::<f32>() * 100.0 < *percent
+            }
+            FilterExpr::RootDistance(range) => {
+                let root = &context.branches[context.lineage(branch_index).1];
+                range.contains(branch.pos.distance(root.pos))
+            }
+        }
     }
 }
 
 /// Decides what branches to operate on based upon filtering the iteration value
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug, Default, PartialEq)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum IterationFilter {
     #[default]
@@ -74,13 +243,15 @@ impl IterationFilter {
 }
 
 /// specify when to ignore/include a branch depending on the id value
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug, Default, PartialEq)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum IdFilter {
     #[default]
     Last,
     All,
     Target(u32),
+    /// Branches created by a step assigning this tag
+    Tag(BranchTag),
 }
 
 impl IdFilter {
@@ -89,6 +260,7 @@ impl IdFilter {
             IdFilter::All => true,
             IdFilter::Last => branch.id == last_generation,
             IdFilter::Target(target_gen) => branch.id == *target_gen,
+            IdFilter::Tag(tag) => branch.tags & tag.mask != 0,
         }
     }
 }
@@ -0,0 +1,99 @@
+//! traversal of the branch tree built by a [`ShrubberyGenerator`]
+//!
+//! Everything is computed on demand from `Branch::parent_index`. Growth only
+//! appends branches after their parent, which the traversals rely on.
+use crate::shrubbery::ShrubberyGenerator;
+
+impl ShrubberyGenerator {
+    /// Indices of the branches grown directly from `branch_index`, in growth order.
+    pub fn children(&self, branch_index: usize) -> impl Iterator<Item = usize> + '_ {
+        self.branches
+            .iter()
+            .enumerate()
+            .skip(branch_index + 1)
+            .filter(move |(_, branch)| branch.parent_index == Some(branch_index))
+            .map(|(index, _)| index)
+    }
+
+    /// Indices of every branch below `branch_index`, in growth order.
+    pub fn descendants(&self, branch_index: usize) -> impl Iterator<Item = usize> + '_ {
+        let mut in_subtree = vec![false; self.branches.len()];
+        if let Some(root) = in_subtree.get_mut(branch_index) {
+            *root = true;
+        }
+        (branch_index + 1..self.branches.len()).filter(move |&index| {
+            let below = self.branches[index]
+                .parent_index
+                .is_some_and(|parent| parent < index && in_subtree[parent]);
+            in_subtree[index] = below;
+            below
+        })
+    }
+
+    /// Indices from the parent of `branch_index` up to and including its root.
+    pub fn ancestors(&self, branch_index: usize) -> impl Iterator<Item = usize> + '_ {
+        let parent = self
+            .branches
+            .get(branch_index)
+            .and_then(|branch| branch.parent_index);
+        std::iter::successors(parent, |&index| self.branches.get(index)?.parent_index)
+    }
+
+    /// Number of ancestors of `branch_index`, 0 for a root.
+    pub fn depth(&self, branch_index: usize) -> u32 {
+        self.ancestors(branch_index).count() as u32
+    }
+
+    /// Strahler order of every branch, indexed like [`Self::branches`].
+    ///
+    /// Tips are order 1. A branch takes the highest order among its children,
+    /// plus one when two or more children share that order.
+    pub fn strahler_orders(&self) -> Vec<u32> {
+        let mut orders = vec![1; self.branches.len()];
+        // (highest child order, children with that order)
+        let mut highest = vec![(0_u32, 0_u32); self.branches.len()];
+        for index in (0..self.branches.len()).rev() {
+            let (order, count) = highest[index];
+            if count > 0 {
+                orders[index] = if count > 1 { order + 1 } else { order };
+            }
+            if let Some(parent) = self.branches[index].parent_index
+                && parent < index
+            {
+                let parent_highest = &mut highest[parent];
+                if orders[index] > parent_highest.0 {
+                    *parent_highest = (orders[index], 1);
+                } else if orders[index] == parent_highest.0 {
+                    parent_highest.1 += 1;
+                }
+            }
+        }
+        orders
+    }
+
+    /// Horton order of every branch, indexed like [`Self::branches`].
+    ///
+    /// Starts from [`Self::strahler_orders`], then every branch hands its order
+    /// down the child with the highest Strahler order (the first grown on ties),
+    /// so a whole stem from root to tip shares one order.
+    pub fn horton_orders(&self) -> Vec<u32> {
+        let strahler = self.strahler_orders();
+        let mut main_child: Vec<Option<usize>> = vec![None; self.branches.len()];
+        for (index, branch) in self.branches.iter().enumerate() {
+            if let Some(parent) = branch.parent_index
+                && parent < index
+                && main_child[parent].is_none_or(|main| strahler[index] > strahler[main])
+            {
+                main_child[parent] = Some(index);
+            }
+        }
+
+        let mut orders = strahler.clone();
+        for index in 0..self.branches.len() {
+            if let Some(main) = main_child[index] {
+                orders[main] = orders[index];
+            }
+        }
+        orders
+    }
+}
@@ -2,11 +2,19 @@
 
 pub mod attractor;
 pub mod branch;
+pub mod error;
 pub mod filter;
+pub mod hierarchy;
+pub mod mesh;
+mod noise;
 pub mod shape;
 pub mod shrubbery;
+mod spatial;
+pub mod tag;
+pub mod validation;
 pub mod value_or_range;
 pub mod voxel;
+pub mod voxel_grid;
 
 pub use glam;
 
@@ -16,10 +24,14 @@ pub mod bevy_debug_draw;
 pub mod bevy_plugin;
 
 pub mod prelude {
-    pub use crate::filter::{Filter, IdFilter, IterationFilter};
+    pub use crate::error::{ShrubberyError, VoxelGridError};
+    pub use crate::filter::{Filter, FilterExpr, IdFilter, IterationFilter};
+    pub use crate::mesh::VoxelMesh;
     pub use crate::shrubbery::{ShrubberyGenerator, ShrubberySettings, ShrubberyStep};
+    pub use crate::tag::BranchTag;
     pub use crate::value_or_range::{ValueOrRangeF32, ValueOrRangeU32};
     pub use crate::voxel::{VoxelDefinitions, VoxelId, VoxelMapping};
+    pub use crate::voxel_grid::VoxelGrid;
 
     #[cfg(feature = "bevy")]
     pub use crate::bevy_debug_draw::{
@@ -27,5 +39,5 @@ pub mod prelude {
         ShrubberyDebugGizmoGroup,
     };
     #[cfg(feature = "bevy")]
-    pub use crate::bevy_plugin::ShrubberyPlugin;
+    pub use crate::bevy_plugin::{ShrubberyPlugin, ShrubberyTree, VoxelColors};
 }
@@ -0,0 +1,148 @@
+//! engine agnostic greedy meshing of voxel output
+//! turns a [`VoxelGrid`] into merged quads, no cargo features required
+use glam::{IVec3, Vec3};
+
+use crate::{voxel::VoxelId, voxel_grid::VoxelGrid};
+
+/// Indexed triangle mesh made of axis aligned quads.
+///
+/// The voxel at `pos` spans `pos..pos + 1` in mesh space. Triangles wind
+/// counter-clockwise when seen from the front face.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct VoxelMesh {
+    pub positions: Vec<[f32; 3]>,
+    pub normals: Vec<[f32; 3]>,
+    /// In voxel units, so a texture repeats once per voxel across merged quads.
+    pub uvs: Vec<[f32; 2]>,
+    pub indices: Vec<u32>,
+    /// Voxel id of each quad, quad `i` owns vertices `4 * i..4 * i + 4`.
+    pub face_voxels: Vec<VoxelId>,
+}
+
+impl VoxelMesh {
+    pub fn quad_count(&self) -> usize {
+        self.face_voxels.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.face_voxels.is_empty()
+    }
+
+    /// [`Self::face_voxels`] repeated for every vertex, for per-vertex attributes.
+    pub fn vertex_voxels(&self) -> impl Iterator<Item = VoxelId> + '_ {
+        self.face_voxels
+            .iter()
+            .flat_map(|voxel_id| std::iter::repeat_n(*voxel_id, 4))
+    }
+
+    fn push_quad(&mut self, corner: Vec3, du: Vec3, dv: Vec3, normal: Vec3, voxel_id: VoxelId) {
+        let base = self.positions.len() as u32;
+        let (w, h) = (du.length(), dv.length());
+        self.positions
+            .extend([corner, corner + du, corner + du + dv, corner + dv].map(|p| p.to_array()));
+        self.normals.extend([normal.to_array(); 4]);
+        self.uvs.extend([[0.0, 0.0], [w, 0.0], [w, h], [0.0, h]]);
+        // du x dv points along +axis, flip the winding for faces looking down it
+        let quad = if du.cross(dv).dot(normal) > 0.0 {
+            [0, 1, 2, 0, 2, 3]
+        } else {
+            [0, 2, 1, 0, 3, 2]
+        };
+        self.indices.extend(quad.map(|i| base + i));
+        self.face_voxels.push(voxel_id);
+    }
+}
+
+impl VoxelGrid {
+    /// Mesh every exposed voxel face, merging coplanar neighbours of the same
+    /// voxel id into larger quads. Faces between two filled voxels are culled.
+    pub fn greedy_mesh(&self) -> VoxelMesh {
+        let mut mesh = VoxelMesh::default();
+        let size = self.size().as_ivec3();
+        let cells = self.cells();
+        let palette = self.palette();
+        let cell_at = |local: IVec3| -> u32 {
+            if local.cmplt(IVec3::ZERO).any() || local.cmpge(size).any() {
+                return 0;
+            }
+            cells[(local.x + size.x * (local.y + size.y * local.z)) as usize]
+        };
+
+        for axis in 0..3 {
+            let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+            let (size_u, size_v) = (size[u], size[v]);
+            let mut mask = vec![0u32; (size_u * size_v).max(0) as usize];
+            for sign in [1, -1] {
+                let mut normal = IVec3::ZERO;
+                normal[axis] = sign;
+                for slice in 0..size[axis] {
+                    // faces of this slice whose neighbour along `normal` is empty
+                    for j in 0..size_v {
+                        for i in 0..size_u {
+                            let mut local = IVec3::ZERO;
+                            local[axis] = slice;
+                            local[u] = i;
+                            local[v] = j;
+                            let cell = cell_at(local);
+                            mask[(i + j * size_u) as usize] =
+                                if cell != 0 && cell_at(local + normal) == 0 {
+                                    cell
+                                } else {
+                                    0
+                                };
+                        }
+                    }
+
+                    for j in 0..size_v {
+                        let mut i = 0;
+                        while i < size_u {
+                            let cell = mask[(i + j * size_u) as usize];
+                            if cell == 0 {
+                                i += 1;
+                                continue;
+                            }
+                            let mut width = 1;
+                            while i + width < size_u
+                                && mask[(i + width + j * size_u) as usize] == cell
+                            {
+                                width += 1;
+                            }
+                            let mut height = 1;
+                            'grow: while j + height < size_v {
+                                for k in 0..width {
+                                    if mask[(i + k + (j + height) * size_u) as usize] != cell {
+                                        break 'grow;
+                                    }
+                                }
+                                height += 1;
+                            }
+                            for dj in 0..height {
+                                for di in 0..width {
+                                    mask[(i + di + (j + dj) * size_u) as usize] = 0;
+                                }
+                            }
+
+                            let mut corner = self.origin().as_vec3();
+                            corner[axis] += (slice + i32::from(sign > 0)) as f32;
+                            corner[u] += i as f32;
+                            corner[v] += j as f32;
+                            let mut du = Vec3::ZERO;
+                            du[u] = width as f32;
+                            let mut dv = Vec3::ZERO;
+                            dv[v] = height as f32;
+                            mesh.push_quad(
+                                corner,
+                                du,
+                                dv,
+                                normal.as_vec3(),
+                                palette[cell as usize - 1],
+                            );
+                            i += width;
+                        }
+                    }
+                }
+            }
+        }
+        mesh
+    }
+}
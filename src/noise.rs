@@ -0,0 +1,63 @@
+//! seeded coherent noise used to perturb growth directions
+use glam::{IVec3, Quat, Vec3, ivec3};
+
+const GRADIENTS: [Vec3; 12] = [
+    Vec3::new(1.0, 1.0, 0.0),
+    Vec3::new(-1.0, 1.0, 0.0),
+    Vec3::new(1.0, -1.0, 0.0),
+    Vec3::new(-1.0, -1.0, 0.0),
+    Vec3::new(1.0, 0.0, 1.0),
+    Vec3::new(-1.0, 0.0, 1.0),
+    Vec3::new(1.0, 0.0, -1.0),
+    Vec3::new(-1.0, 0.0, -1.0),
+    Vec3::new(0.0, 1.0, 1.0),
+    Vec3::new(0.0, -1.0, 1.0),
+    Vec3::new(0.0, 1.0, -1.0),
+    Vec3::new(0.0, -1.0, -1.0),
+];
+
+/// splitmix64 finalizer, spreads every input bit over the output
+pub(crate) fn mix(mut x: u64) -> u64 {
+    x = x.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    x ^ (x >> 31)
+}
+
+fn gradient(seed: u64, cell: IVec3) -> Vec3 {
+    let hash = mix(seed ^ mix(cell.x as u64 ^ mix(cell.y as u64 ^ mix(cell.z as u64))));
+    GRADIENTS[(hash % GRADIENTS.len() as u64) as usize]
+}
+
+fn fade(t: Vec3) -> Vec3 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+/// Gradient noise in -1.0..=1.0, smooth across `p` and fixed for a `seed`.
+pub(crate) fn perlin(seed: u64, p: Vec3) -> f32 {
+    let cell = p.floor();
+    let local = p - cell;
+    let cell = cell.as_ivec3();
+    let t = fade(local);
+
+    let corner =
+        |offset: IVec3| -> f32 { gradient(seed, cell + offset).dot(local - offset.as_vec3()) };
+    let lerp = |a: f32, b: f32, t: f32| a + (b - a) * t;
+    let x00 = lerp(corner(ivec3(0, 0, 0)), corner(ivec3(1, 0, 0)), t.x);
+    let x10 = lerp(corner(ivec3(0, 1, 0)), corner(ivec3(1, 1, 0)), t.x);
+    let x01 = lerp(corner(ivec3(0, 0, 1)), corner(ivec3(1, 0, 1)), t.x);
+    let x11 = lerp(corner(ivec3(0, 1, 1)), corner(ivec3(1, 1, 1)), t.x);
+    let y0 = lerp(x00, x10, t.y);
+    let y1 = lerp(x01, x11, t.y);
+    lerp(y0, y1, t.z).clamp(-1.0, 1.0)
+}
+
+/// Rotate `dir` by up to `amplitude` degrees around two axes perpendicular to it,
+/// with the angles read from two independent noise fields at `p`.
+pub(crate) fn wander(seed: u64, p: Vec3, dir: Vec3, amplitude: f32) -> Vec3 {
+    let (axis_a, axis_b) = dir.any_orthonormal_pair();
+    let angle_a = perlin(seed, p) * amplitude.to_radians();
+    let angle_b = perlin(mix(seed), p) * amplitude.to_radians();
+    (Quat::from_axis_angle(axis_a, angle_a) * Quat::from_axis_angle(axis_b, angle_b) * dir)
+        .normalize_or(dir)
+}
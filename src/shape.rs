@@ -1,4 +1,6 @@
 //! attractor spawning shapes
+use std::f32::consts::TAU;
+
 use glam::*;
 use rand::RngExt;
 use rand_chacha::ChaCha8Rng;
@@ -6,16 +8,59 @@ use rand_chacha::ChaCha8Rng;
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
-use crate::{attractor::Attractor, shrubbery::AttractorSpacing};
+use crate::{
+    attractor::Attractor,
+    shrubbery::{AttractorDistribution, AttractorSpacing},
+    spatial::SpatialGrid,
+    value_or_range::random_range_f32,
+    voxel::point_segment_distance_squared,
+};
 
 /// The shape that is used to spawn Attractors
+/// Sizes are full lengths, radii are half lengths. Shapes are centered on the spawn location.
 #[derive(Clone, Debug)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum AttractorShape {
     Cube(CubeShape),
+    Sphere {
+        radius: f32,
+    },
+    Ellipsoid {
+        radius_x: f32,
+        radius_y: f32,
+        radius_z: f32,
+    },
+    /// Upper half of a sphere, the flat side lies on the spawn location
+    Hemisphere {
+        radius: f32,
+    },
+    /// Upright along Y
+    Cylinder {
+        radius: f32,
+        height: f32,
+    },
+    /// Upright along Y, base at the bottom, apex at the top
+    Cone {
+        radius: f32,
+        height: f32,
+    },
+    /// Ring lying flat in the XZ plane
+    Torus {
+        /// ring center to tube center
+        major_radius: f32,
+        /// tube radius
+        minor_radius: f32,
+    },
+    /// Rounded segment between two points, relative to the spawn location
+    Capsule {
+        start: Vec3,
+        end: Vec3,
+        radius: f32,
+    },
 }
 
 impl AttractorShape {
+    /// Spawn attractors filling the shape, see [`AttractorSpacing`].
     pub fn generate(
         &self,
         pos: Vec3,
@@ -23,25 +68,23 @@ impl AttractorShape {
         settings: &AttractorSpacing,
         rng: &mut ChaCha8Rng,
     ) {
-        match self {
-            AttractorShape::Cube(cube) => cube.generate(pos, attractors, settings, rng),
+        match settings.distribution {
+            AttractorDistribution::JitteredGrid => {
+                self.generate_jittered_grid(pos, attractors, settings, rng)
+            }
+            AttractorDistribution::PoissonDisk { attempts } => self.generate_poisson_disk(
+                pos,
+                attractors,
+                settings.attractor_spacing.max(0.001),
+                attempts.max(1),
+                rng,
+            ),
         }
     }
-}
 
-/// Shape for spawning Attractors. `size_(x/y/z)` specify full length.
-#[derive(Clone, Debug)]
-#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
-pub struct CubeShape {
-    pub size_x: f32,
-    pub size_y: f32,
-    pub size_z: f32,
-}
-
-impl CubeShape {
-    fn generate(
+    fn generate_jittered_grid(
         &self,
-        shape_pos: Vec3,
+        pos: Vec3,
         attractors: &mut Vec<Attractor>,
         settings: &AttractorSpacing,
         rng: &mut ChaCha8Rng,
@@ -49,10 +92,9 @@ impl CubeShape {
         let spacing = settings.attractor_spacing.max(0.001);
         let jitter_ratio = settings.jitter_ratio.clamp(0.0, 1.0);
         let scatter = spacing * 0.5 * jitter_ratio;
-        let center_offset = -vec3(self.size_x, self.size_y, self.size_z) * 0.5;
+        let (min, max) = self.bounds();
 
-        let [nx, ny, nz] =
-            [self.size_x, self.size_y, self.size_z].map(|v| (v / spacing).ceil() as i32);
+        let [nx, ny, nz] = (max - min).to_array().map(|v| (v / spacing).ceil() as i32);
 
         for x in 0..nx {
             for y in 0..ny {
@@ -62,14 +104,204 @@ impl CubeShape {
                         (y as f32 + 0.5) * spacing,
                         (z as f32 + 0.5) * spacing,
                     );
+                    // jitter is rolled for every cell, so rejected cells don't shift the rng
                     let jitter = vec3(
-                        rng.random_range(-scatter..=scatter),
-                        rng.random_range(-scatter..=scatter),
-                        rng.random_range(-scatter..=scatter),
+                        random_range_f32(rng, -scatter, scatter),
+                        random_range_f32(rng, -scatter, scatter),
+                        random_range_f32(rng, -scatter, scatter),
                     );
-                    attractors.push(Attractor::new(shape_pos + cell + center_offset + jitter));
+                    // cubes keep jitter poking out of the box, as they always have
+                    let is_cube = matches!(self, AttractorShape::Cube(_));
+                    if !is_cube && !self.contains(cell + min + jitter) {
+                        continue;
+                    }
+                    attractors.push(Attractor::new(pos + cell + min + jitter));
+                }
+            }
+        }
+    }
+
+    /// Bridson's algorithm: grow outwards from a random seed point, trying
+    /// `attempts` candidates between `min_distance` and twice that around each
+    /// placed attractor, until no attractor can place a neighbour.
+    fn generate_poisson_disk(
+        &self,
+        pos: Vec3,
+        attractors: &mut Vec<Attractor>,
+        min_distance: f32,
+        attempts: u32,
+        rng: &mut ChaCha8Rng,
+    ) {
+        let (min, max) = self.bounds();
+        let Some(seed_point) = (0..attempts)
+            .map(|_| {
+                vec3(
+                    random_range_f32(rng, min.x, max.x),
+                    random_range_f32(rng, min.y, max.y),
+                    random_range_f32(rng, min.z, max.z),
+                )
+            })
+            .find(|p| self.contains(*p))
+        else {
+            return;
+        };
+
+        let mut points = vec![seed_point];
+        let mut active = vec![0];
+        let mut grid = SpatialGrid::new(min_distance);
+        grid.insert(0, seed_point);
+        while !active.is_empty() {
+            let active_index = rng.random_range(0..active.len());
+            let origin = points[active[active_index]];
+            let mut placed = false;
+            for _ in 0..attempts {
+                // uniform direction on the unit sphere
+                let z = random_range_f32(rng, -1.0, 1.0);
+                let angle = random_range_f32(rng, 0.0, TAU);
+                let ring = (1.0 - z * z).sqrt();
+                let dir = vec3(ring * angle.cos(), ring * angle.sin(), z);
+                let candidate = origin + dir * min_distance * (1.0 + rng.random::<f32>());
+                if !self.contains(candidate) {
+                    continue;
                 }
+                let too_close = grid
+                    .neighbours(candidate)
+                    .any(|i| points[i].distance_squared(candidate) < min_distance * min_distance);
+                if too_close {
+                    continue;
+                }
+                grid.insert(points.len(), candidate);
+                active.push(points.len());
+                points.push(candidate);
+                placed = true;
+                break;
+            }
+            if !placed {
+                active.swap_remove(active_index);
             }
         }
+        attractors.extend(points.into_iter().map(|p| Attractor::new(pos + p)));
     }
+
+    /// Min and max corner of the shape's bounding box, relative to the spawn location.
+    pub fn bounds(&self) -> (Vec3, Vec3) {
+        let (min, max) = match self {
+            AttractorShape::Cube(cube) => {
+                let half = vec3(cube.size_x, cube.size_y, cube.size_z) * 0.5;
+                (-half, half)
+            }
+            AttractorShape::Sphere { radius } => (Vec3::splat(-radius), Vec3::splat(*radius)),
+            AttractorShape::Ellipsoid {
+                radius_x,
+                radius_y,
+                radius_z,
+            } => {
+                let half = vec3(*radius_x, *radius_y, *radius_z);
+                (-half, half)
+            }
+            AttractorShape::Hemisphere { radius } => {
+                (vec3(-radius, 0.0, -radius), Vec3::splat(*radius))
+            }
+            AttractorShape::Cylinder { radius, height }
+            | AttractorShape::Cone { radius, height } => {
+                let half = vec3(*radius, height * 0.5, *radius);
+                (-half, half)
+            }
+            AttractorShape::Torus {
+                major_radius,
+                minor_radius,
+            } => {
+                let outer = major_radius + minor_radius;
+                let half = vec3(outer, *minor_radius, outer);
+                (-half, half)
+            }
+            AttractorShape::Capsule { start, end, radius } => (
+                start.min(*end) - Vec3::splat(*radius),
+                start.max(*end) + Vec3::splat(*radius),
+            ),
+        };
+        // negative sizes would flip the box, treat them as empty instead
+        (min, max.max(min))
+    }
+
+    /// Is `local`, relative to the spawn location, inside the shape.
+    pub fn contains(&self, local: Vec3) -> bool {
+        match self {
+            AttractorShape::Cube(cube) => (local * 2.0)
+                .abs()
+                .cmple(vec3(cube.size_x, cube.size_y, cube.size_z))
+                .all(),
+            AttractorShape::Sphere { radius } => local.length_squared() <= radius * radius,
+            AttractorShape::Ellipsoid {
+                radius_x,
+                radius_y,
+                radius_z,
+            } => (local / vec3(*radius_x, *radius_y, *radius_z)).length_squared() <= 1.0,
+            AttractorShape::Hemisphere { radius } => {
+                local.y >= 0.0 && local.length_squared() <= radius * radius
+            }
+            AttractorShape::Cylinder { radius, height } => {
+                local.y.abs() <= height * 0.5 && local.xz().length_squared() <= radius * radius
+            }
+            AttractorShape::Cone { radius, height } => {
+                // 0 at the base, 1 at the apex
+                let t = local.y / height + 0.5;
+                let allowed = radius * (1.0 - t);
+                (0.0..=1.0).contains(&t) && local.xz().length_squared() <= allowed * allowed
+            }
+            AttractorShape::Torus {
+                major_radius,
+                minor_radius,
+            } => {
+                let ring_offset = vec2(local.xz().length() - major_radius, local.y);
+                ring_offset.length_squared() <= minor_radius * minor_radius
+            }
+            AttractorShape::Capsule { start, end, radius } => {
+                point_segment_distance_squared(local, *start, *end) <= radius * radius
+            }
+        }
+    }
+}
+
+/// How densely attractors fill a shape, as the chance (0-1) to keep each one.
+/// Positions are normalized to the shape's bounding box, `-1..1` on each axis.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum AttractorDensity {
+    #[default]
+    Uniform,
+    /// Denser toward the outside, `distance_from_centre ^ power`
+    Shell { power: f32 },
+    /// Denser toward the top, `height ^ power` with the bottom at 0 and the top at 1
+    Top { power: f32 },
+    /// Denser toward the centre, `(1 - distance_from_centre) ^ power`
+    RadialFalloff { power: f32 },
+}
+
+impl AttractorDensity {
+    /// Keep chance for an attractor at `local`, relative to the shape's spawn location.
+    pub fn get(&self, shape: &AttractorShape, local: Vec3) -> f32 {
+        let (min, max) = shape.bounds();
+        let half_extents = ((max - min) * 0.5).max(Vec3::splat(f32::EPSILON));
+        let normalized = (local - (min + max) * 0.5) / half_extents;
+        let distance = normalized.length().min(1.0);
+        let density = match self {
+            AttractorDensity::Uniform => 1.0,
+            AttractorDensity::Shell { power } => distance.powf(*power),
+            AttractorDensity::Top { power } => {
+                ((normalized.y + 1.0) * 0.5).clamp(0.0, 1.0).powf(*power)
+            }
+            AttractorDensity::RadialFalloff { power } => (1.0 - distance).powf(*power),
+        };
+        density.clamp(0.0, 1.0)
+    }
+}
+
+/// Shape for spawning Attractors. `size_(x/y/z)` specify full length.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct CubeShape {
+    pub size_x: f32,
+    pub size_y: f32,
+    pub size_z: f32,
 }
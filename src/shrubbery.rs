@@ -1,12 +1,18 @@
 //! the core implementation for generating fine shrubberies
-use ahash::HashSet;
-
 use crate::{
     attractor::Attractor,
     branch::Branch,
+    filter::FilterContext,
+    noise,
     prelude::*,
-    shape::AttractorShape,
-    voxel::{DecorationSelector, Shape, VoxelDefinitions},
+    shape::{AttractorDensity, AttractorShape},
+    spatial::SpatialGrid,
+    tag::BranchTag,
+    value_or_range::random_range_f32,
+    voxel::{
+        DecorationSelector, Shape, VoxelDefinitions, point_segment_distance_squared,
+        round_cone_distance,
+    },
 };
 
 use glam::{IVec3, Quat, Vec3, ivec3, vec3};
@@ -25,6 +31,12 @@ pub enum BranchSpawnMethod {
     Single,
     /// Spawn multiple branches in a radial formation
     GrowRadial(GrowRadial),
+    /// Spawn branches at points along the selected segments, not only at their end
+    GrowLateral(GrowLateral),
+    /// Spawn branches in a botanical arrangement, rotating from node to node
+    Phyllotaxis(Phyllotaxis),
+    /// Split every tip into several children, every iteration
+    Fork(Fork),
 }
 
 /// data for [`BranchSpawnMethod::GrowRadial`]
@@ -62,17 +74,27 @@ pub enum ShrubberyStep {
     Grow(GrowStep),
     /// Spawn attractors, based upon `shape` and `location`
     SpawnAttractors(SpawnAttractors),
+    /// Delete attractors inside a shape, carving holes into attractor volumes
+    RemoveAttractors(RemoveAttractors),
     /// delete ALL generated attractor points
     ClearAttractors,
+    /// delete the attractor points of one named set, see [`SpawnAttractors::set`]
+    ClearAttractorSet(String),
     /// Assign a leaf shape to branches
     /// Already assigned leaf groups are skipped unless `overwrite` is true
     Shape(ShapeStep),
+    /// Recompute every branch thickness from the tips down, see [`ComputeThickness`]
+    ComputeThickness(ComputeThickness),
 }
 
 /// The core build instructions describing how to make a fine shrubbery
 #[derive(Clone, Debug, Default)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct ShrubberySettings {
+    #[cfg_attr(
+        feature = "serde",
+        serde(deserialize_with = "crate::tag::deserialize_build_steps")
+    )]
     pub build_steps: Vec<ShrubberyStep>,
 }
 
@@ -88,7 +110,7 @@ pub enum AssignBranchId {
 impl AssignBranchId {
     pub fn get(&self, last_id: u32) -> u32 {
         match self {
-            AssignBranchId::AutoIncrement => last_id + 1,
+            AssignBranchId::AutoIncrement => last_id.saturating_add(1),
             AssignBranchId::AssignId(id) => *id,
         }
     }
@@ -111,14 +133,158 @@ pub struct GrowStep {
     pub length: ValueOrRangeF32,
     /// Branch thickness
     pub thickness: BranchThickness,
+    /// How the grown segments are rasterized
+    pub curve: BranchCurve,
     /// How voxels are decorated
     pub voxel: DecorationSelector,
     /// How to assign id to branches (used for Filtering)
     pub id: AssignBranchId,
+    /// Tags assigned to the new branches (used for Filtering)
+    pub tags: Vec<BranchTag>,
     /// Filters what branches to grow from
     pub filter: Filter,
 }
 
+/// data for [`BranchSpawnMethod::GrowLateral`]
+/// describes how to sprout side branches along a segment
+///
+/// Side branches are children of the segment, starting at their
+/// [`Branch::start_pos`] on it with the segment's radius at that point.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(default))]
+pub struct GrowLateral {
+    /// side branches per world unit of segment length, rounded down per segment
+    pub density: f32,
+    /// where the side branches sit along the segment
+    pub placement: LateralPlacement,
+    /// angle between the segment and a side branch, in degrees
+    pub pitch_degrees: ValueOrRangeF32,
+}
+
+impl Default for GrowLateral {
+    fn default() -> Self {
+        Self {
+            density: 0.5,
+            placement: LateralPlacement::default(),
+            pitch_degrees: ValueOrRangeF32::Value(45.0),
+        }
+    }
+}
+
+/// where [`GrowLateral`] places side branches along a segment
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum LateralPlacement {
+    /// equal spacing, centred on the segment
+    #[default]
+    Even,
+    /// uniformly random positions
+    Random,
+}
+
+/// data for [`BranchSpawnMethod::Phyllotaxis`]
+/// describes how branches are arranged around successive nodes
+///
+/// Each selected branch is a node. Its branches are spread evenly around its
+/// direction, and every node further from the root is rotated by the divergence
+/// angle, so a chain of nodes forms the arrangement.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(default))]
+pub struct Phyllotaxis {
+    pub arrangement: PhyllotaxisArrangement,
+    /// rotation between successive nodes in degrees, overrides the arrangement's
+    pub divergence_degrees: Option<f32>,
+    /// angle between the node's direction and a new branch, in degrees
+    pub branch_angle_degrees: ValueOrRangeF32,
+}
+
+impl Default for Phyllotaxis {
+    fn default() -> Self {
+        Self {
+            arrangement: PhyllotaxisArrangement::default(),
+            divergence_degrees: None,
+            branch_angle_degrees: ValueOrRangeF32::Value(45.0),
+        }
+    }
+}
+
+/// botanical branch arrangements for [`Phyllotaxis`]
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum PhyllotaxisArrangement {
+    /// one branch per node, alternating sides (180 degrees)
+    Alternate,
+    /// two facing branches per node, pairs crossed at 90 degrees
+    Opposite,
+    /// `count` branches per node, whorls offset by half their spacing
+    Whorled { count: u32 },
+    /// one branch per node, turned by the golden angle (~137.5 degrees)
+    #[default]
+    Spiral,
+}
+
+impl PhyllotaxisArrangement {
+    pub const GOLDEN_ANGLE_DEGREES: f32 = 137.507_76;
+
+    /// branches per node
+    pub fn count(&self) -> u32 {
+        match self {
+            Self::Alternate | Self::Spiral => 1,
+            Self::Opposite => 2,
+            Self::Whorled { count } => *count,
+        }
+    }
+
+    /// rotation between successive nodes in degrees
+    pub fn divergence_degrees(&self) -> f32 {
+        match self {
+            Self::Alternate => 180.0,
+            Self::Opposite => 90.0,
+            Self::Whorled { count } => 180.0 / (*count).max(1) as f32,
+            Self::Spiral => Self::GOLDEN_ANGLE_DEGREES,
+        }
+    }
+}
+
+/// data for [`BranchSpawnMethod::Fork`]
+/// describes how tips split on every `times` iteration
+///
+/// The new tips are the active set of the next iteration, so a single step
+/// grows a fractal bush. A tip that does not fork grows one segment using the
+/// step's `dir` instead.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(default))]
+pub struct Fork {
+    /// children per forking tip
+    pub count: ValueOrRangeU32,
+    /// angle between the tip's direction and each child, in degrees
+    pub spread_degrees: ValueOrRangeF32,
+    /// percent chance (0.0-100.0) that a tip forks in an iteration
+    pub chance: f32,
+}
+
+impl Default for Fork {
+    fn default() -> Self {
+        Self {
+            count: ValueOrRangeU32::Value(2),
+            spread_degrees: ValueOrRangeF32::Value(30.0),
+            chance: 100.0,
+        }
+    }
+}
+
+/// How the segments of a [`GrowStep`] are rasterized
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum BranchCurve {
+    /// one capsule from the parent to the branch position
+    #[default]
+    Straight,
+    /// a smooth Catmull-Rom curve through the chain's branch positions, split into
+    /// `subdivisions` capsules per segment that follow the segment's taper
+    CatmullRom { subdivisions: u32 },
+}
+
 /// Describes how to select thickness value for [`Branch`]
 #[derive(Clone, Debug)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
@@ -153,6 +319,15 @@ impl BranchThickness {
             }
         }
     }
+
+    /// Radius at the parent end of segment `i` of a chain. `IterationScale`
+    /// continues from the previous segment, so the chain tapers without steps.
+    pub fn start(&self, i: u32, parent_thickness: f32, thickness: f32) -> f32 {
+        match self {
+            BranchThickness::IterationScale { .. } if i > 0 => parent_thickness,
+            _ => thickness,
+        }
+    }
 }
 
 /// describes what direction a branch should grow when executing [`ShrubberyStep::Grow`] commands
@@ -168,6 +343,18 @@ pub enum BranchGrowthDirection {
     WorldPos(Vec3),
     /// Grow from dir, (derived from parent normal), but apply gravity on Y axis
     GravityLean { strength: f32 },
+    /// Grow from dir, (derived from parent normal), bent toward a world direction
+    Tropism(Tropism),
+    /// Grow from dir, (derived from parent normal), turned toward `dir` by at most
+    /// `max_degrees` per segment
+    SteerToward { dir: Vec3, max_degrees: f32 },
+    /// Grow from dir, (derived from parent normal), rotated by seeded coherent noise
+    Wander {
+        /// largest rotation per segment in degrees
+        amplitude: f32,
+        /// noise features per world unit, lower values give longer smoother bends
+        frequency: f32,
+    },
     /// Grow branches using space-colonization towards attractor points
     Attractor(AttractorSettings),
 }
@@ -183,6 +370,17 @@ pub struct AttractorSettings {
     pub attract_distance: f32,
     /// Describes how to assign iteration value
     pub iteration_calculation: IterationCalculation,
+    /// Names of the attractor sets to grow towards, empty grows towards all of them
+    pub sets: Vec<String>,
+    /// Half angle in degrees of the cone around a node's heading it perceives
+    /// attractors in, 180 sees all around
+    pub perception_angle: f32,
+    /// Which nodes may sprout new segments each iteration
+    pub growth: AttractorGrowth,
+    /// Attractors that must pull a node before it grows, values below 1 act as 1
+    pub min_attractors: u32,
+    /// Bends the attractor pull toward a world direction, e.g. wind or gravity
+    pub tropism: Option<Tropism>,
 }
 
 impl Default for AttractorSettings {
@@ -191,10 +389,74 @@ impl Default for AttractorSettings {
             kill_distance: 5.0,
             attract_distance: 10.0,
             iteration_calculation: IterationCalculation::default(),
+            sets: Vec::new(),
+            perception_angle: 180.0,
+            growth: AttractorGrowth::default(),
+            min_attractors: 1,
+            tropism: None,
         }
     }
 }
 
+/// bends growth toward a world direction, light (up), gravity (down) or wind
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Tropism {
+    /// World direction to bend toward (normalized internally)
+    pub dir: Vec3,
+    /// How far to blend toward `dir`, 0.0 keeps the parent dir, 1.0 grows along `dir`
+    pub strength: TropismStrength,
+}
+
+/// Describes how to select the blend strength of a [`Tropism`]
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum TropismStrength {
+    Value(f32),
+    /// strength is scaled from `start` at the first iteration to `end` at the last
+    IterationScale {
+        start: f32,
+        end: f32,
+    },
+}
+
+impl TropismStrength {
+    pub fn get(&self, i: u32, i_max: u32) -> f32 {
+        match self {
+            TropismStrength::Value(value) => *value,
+            TropismStrength::IterationScale { start, end } => {
+                let t = if i_max <= 1 {
+                    1.0
+                } else {
+                    i as f32 / (i_max - 1) as f32
+                };
+                start + (end - start) * t
+            }
+        }
+    }
+}
+
+impl Tropism {
+    /// Blend `dir` toward [`Self::dir`], keeps `dir` when the blend cancels out
+    pub fn apply(&self, dir: Vec3, i: u32, i_max: u32) -> Vec3 {
+        let strength = self.strength.get(i, i_max).clamp(0.0, 1.0);
+        dir.lerp(self.dir.normalize_or(dir), strength)
+            .try_normalize()
+            .unwrap_or(dir)
+    }
+}
+
+/// which nodes take part in a space-colonization iteration
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum AttractorGrowth {
+    /// only the segments created in the previous iteration
+    #[default]
+    Tips,
+    /// the filtered branches and every segment grown by this step so far
+    AllNodes,
+}
+
 impl BranchGrowthDirection {
     pub fn attractors(&self) -> Option<&AttractorSettings> {
         match self {
@@ -249,6 +511,7 @@ impl InitialDir {
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(default))]
 pub struct SpawnRootStep {
     pub id: AssignBranchId,
+    pub tags: Vec<BranchTag>,
     /// how many initial trunks to spawn
     pub times: ValueOrRangeU32,
     pub pos: Vec3,
@@ -262,6 +525,7 @@ impl Default for SpawnRootStep {
             initial_dir: InitialDir::default(),
             pos: Vec3::ZERO,
             id: AssignBranchId::default(),
+            tags: Vec::new(),
         }
     }
 }
@@ -294,6 +558,33 @@ fn default_spawn_attractor_filter() -> Filter {
     }
 }
 
+/// Grown branch segments bucketed by position, see [`ShrubberyGenerator::branch_volumes`].
+pub struct BranchVolumes<'a> {
+    branches: &'a [Branch],
+    grid: SpatialGrid,
+}
+
+impl BranchVolumes<'_> {
+    /// Is `pos` within the thickness of a segment, tapering from its start to end radius.
+    pub fn contains(&self, pos: Vec3) -> bool {
+        self.grid.neighbours(pos).any(|index| {
+            let branch = &self.branches[index];
+            if branch.start_thickness == branch.thickness {
+                point_segment_distance_squared(pos, branch.start_pos, branch.pos)
+                    < branch.thickness * branch.thickness
+            } else {
+                round_cone_distance(
+                    pos,
+                    branch.start_pos,
+                    branch.pos,
+                    branch.start_thickness,
+                    branch.thickness,
+                ) < 0.0
+            }
+        })
+    }
+}
+
 /// data for [`ShrubberyStep::SpawnAttractors`]
 /// describes where/how attractors should spawn
 #[derive(Clone, Debug)]
@@ -305,6 +596,89 @@ pub struct SpawnAttractors {
     pub shape: AttractorShape,
     #[cfg_attr(feature = "serde", serde(default))]
     pub attractor_spacing: AttractorSpacing,
+    /// Thins attractors out by position inside the shape
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub density: AttractorDensity,
+    /// Pull strength of the spawned attractors, see [`Attractor::weight`]
+    #[cfg_attr(feature = "serde", serde(default = "default_attractor_weight"))]
+    pub weight: f32,
+    /// Named set the attractors are added to, `""` is the default set.
+    /// Lets attractor grow steps pick which attractors they consume.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub set: String,
+    /// Skip attractors spawning inside the thickness of an existing branch,
+    /// they would be reached at once and never pull. On by default.
+    #[cfg_attr(feature = "serde", serde(default = "default_reject_inside_branches"))]
+    pub reject_inside_branches: bool,
+}
+
+/// data for [`ShrubberyStep::ComputeThickness`]
+/// describes the pipe model (Leonardo's rule) used to size branches
+///
+/// Tips get `tip_radius`, every other branch gets `r^n = sum(r_child^n)`. A
+/// segment tapers from its parent's radius when it is the parent's only child.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(default))]
+pub struct ComputeThickness {
+    /// `n` in the rule, 2.0 keeps the cross-section area, higher values thin the trunk
+    pub exponent: f32,
+    /// Radius of branches without children
+    pub tip_radius: f32,
+    /// Upper bound for every radius
+    pub max_radius: f32,
+}
+
+impl Default for ComputeThickness {
+    fn default() -> Self {
+        Self {
+            exponent: 2.0,
+            tip_radius: 0.5,
+            max_radius: 8.0,
+        }
+    }
+}
+
+/// data for [`ShrubberyStep::RemoveAttractors`]
+/// describes where to delete attractors, using the same placement as [`SpawnAttractors`]
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct RemoveAttractors {
+    pub location: SpawnAttractorLocation,
+    pub shape: AttractorShape,
+    /// Sets to remove from, empty removes from all of them
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub sets: Vec<String>,
+}
+
+#[cfg(feature = "serde")]
+fn default_attractor_weight() -> f32 {
+    1.0
+}
+
+#[cfg(feature = "serde")]
+fn default_reject_inside_branches() -> bool {
+    true
+}
+
+impl SpawnAttractors {
+    /// Fill the shape centred on `pos` with attractors, thinned by [`Self::density`].
+    pub fn generate(&self, pos: Vec3, attractors: &mut Vec<Attractor>, rng: &mut ChaCha8Rng) {
+        let start = attractors.len();
+        self.shape
+            .generate(pos, attractors, &self.attractor_spacing, rng);
+        // uniform density skips the keep rolls, leaving the rng untouched
+        if !matches!(self.density, AttractorDensity::Uniform) {
+            let mut spawned = attractors.split_off(start);
+            spawned.retain(|attractor| {
+                let keep_chance = self.density.get(&self.shape, attractor.pos - pos);
+                rng.random::<f32>() < keep_chance
+            });
+            attractors.append(&mut spawned);
+        }
+        for attractor in &mut attractors[start..] {
+            attractor.weight = self.weight;
+        }
+    }
 }
 
 /// the chances that a `ShrubberyStep` will execute
@@ -322,7 +696,10 @@ impl StepChance {
             StepChance::Always => true,
             StepChance::Chance(chance) => {
                 let percent = chance / 100.0;
-                rng.random_bool(percent as f64)
+                if percent.is_nan() {
+                    return false;
+                }
+                rng.random_bool((percent as f64).clamp(0.0, 1.0))
             }
         }
     }
@@ -392,7 +769,10 @@ impl ShrubberySettings {
                     spawn_leaves_step.voxel.resolve(voxel_definitions);
                 }
                 ShrubberyStep::SpawnAttractors(_)
+                | ShrubberyStep::RemoveAttractors(_)
                 | ShrubberyStep::ClearAttractors
+                | ShrubberyStep::ClearAttractorSet(_)
+                | ShrubberyStep::ComputeThickness(_)
                 | ShrubberyStep::SpawnRoot(_) => (),
             }
         }
@@ -408,6 +788,8 @@ pub struct AttractorSpacing {
     // how far to randomize the attractor positions.
     // 0.0: perfect grid spacing. 1.0: attractors can just touch.
     pub jitter_ratio: f32,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub distribution: AttractorDistribution,
 }
 
 impl Default for AttractorSpacing {
@@ -415,10 +797,32 @@ impl Default for AttractorSpacing {
         Self {
             attractor_spacing: 5.0,
             jitter_ratio: 1.0,
+            distribution: AttractorDistribution::default(),
         }
     }
 }
 
+/// How attractors are placed inside an [`AttractorShape`]
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum AttractorDistribution {
+    /// One attractor per `attractor_spacing` grid cell, offset by `jitter_ratio`
+    #[default]
+    JitteredGrid,
+    /// Blue noise, no two attractors closer than `attractor_spacing`. `jitter_ratio` is unused.
+    PoissonDisk {
+        /// candidates tried around each attractor before giving up on it,
+        /// higher packs the shape more tightly
+        #[cfg_attr(feature = "serde", serde(default = "default_poisson_attempts"))]
+        attempts: u32,
+    },
+}
+
+#[cfg(feature = "serde")]
+fn default_poisson_attempts() -> u32 {
+    30
+}
+
 /// describes how to assign iteration value
 #[derive(Clone, Debug, Default)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
@@ -445,6 +849,16 @@ pub struct ShrubberyGenerator {
     pub leaf_groups: Vec<(Shape, DecorationSelector)>,
     /// One entry per growth step, indexed by `Branch::decoration_group`.
     pub branch_decorations: Vec<DecorationSelector>,
+    /// Build step index that registered each `leaf_groups` entry, for error reporting.
+    pub leaf_group_steps: Vec<usize>,
+    /// Build step index that registered each `branch_decorations` entry, for error reporting.
+    pub branch_decoration_steps: Vec<usize>,
+    /// How each growth step's segments are rasterized, indexed by `Branch::decoration_group`.
+    pub branch_curves: Vec<BranchCurve>,
+    /// How many steps [`Self::execute_step`] has run, the index of the next step.
+    pub step_index: usize,
+    /// Attractor set names, indexed by `Attractor::set`. Index 0 is the default set `""`.
+    pub attractor_sets: Vec<String>,
 
     pub last_known_id: u32,
 }
@@ -460,6 +874,11 @@ impl ShrubberyGenerator {
             seed,
             leaf_groups: Vec::new(),
             branch_decorations: Vec::new(),
+            leaf_group_steps: Vec::new(),
+            branch_decoration_steps: Vec::new(),
+            branch_curves: Vec::new(),
+            step_index: 0,
+            attractor_sets: vec![String::new()],
             last_known_id: 0,
         }
     }
@@ -471,27 +890,32 @@ impl ShrubberyGenerator {
         generator
     }
 
-    fn branch_indices_filtered_vec(&self, filter: &Filter) -> Vec<usize> {
-        self.branches
-            .iter()
-            .enumerate()
-            .filter_map(|(i, branch)| {
-                filter
-                    .should_include_branch(branch, self.last_known_id)
-                    .then_some(i)
-            })
-            .collect()
+    /// Index of the attractor set `name`, registering it on first use.
+    pub fn attractor_set_index(&mut self, name: &str) -> usize {
+        match self.attractor_sets.iter().position(|set| set == name) {
+            Some(index) => index,
+            None => {
+                self.attractor_sets.push(name.to_string());
+                self.attractor_sets.len() - 1
+            }
+        }
     }
 
-    fn branch_indices_filtered_hashset(&self, filter: &Filter) -> HashSet<usize> {
-        self.branches
-            .iter()
-            .enumerate()
-            .filter_map(|(i, b)| {
-                filter
-                    .should_include_branch(b, self.last_known_id)
-                    .then_some(i)
-            })
+    /// What filters of the currently executing step are evaluated against.
+    pub fn filter_context(&self) -> FilterContext<'_> {
+        FilterContext {
+            branches: &self.branches,
+            last_id: self.last_known_id,
+            seed: self.seed,
+            step_index: self.step_index,
+            lineage: Default::default(),
+        }
+    }
+
+    fn branch_indices_filtered_vec(&self, filter: &Filter) -> Vec<usize> {
+        let context = self.filter_context();
+        (0..self.branches.len())
+            .filter(|&i| filter.should_include_branch(i, &context))
             .collect()
     }
 
@@ -500,38 +924,23 @@ impl ShrubberyGenerator {
             ShrubberyStep::SpawnRoot(spawn_root_branch) => {
                 self.spawn_root_branch(spawn_root_branch);
             }
-            ShrubberyStep::SpawnAttractors(spawn_attractor) => match &spawn_attractor.location {
-                SpawnAttractorLocation::Pos(pos) => {
-                    spawn_attractor.shape.generate(
-                        *pos,
-                        &mut self.attractors,
-                        &spawn_attractor.attractor_spacing,
-                        &mut self.rng,
-                    );
+            ShrubberyStep::SpawnAttractors(spawn_attractor) => {
+                let start = self.attractors.len();
+                self.spawn_attractors(spawn_attractor);
+                if spawn_attractor.reject_inside_branches {
+                    let mut spawned = self.attractors.split_off(start);
+                    let volumes = self.branch_volumes();
+                    spawned.retain(|attractor| !volumes.contains(attractor.pos));
+                    self.attractors.append(&mut spawned);
                 }
-                SpawnAttractorLocation::FromBranch(from_branch) => {
-                    let origins: Vec<(Vec3, Vec3)> = self
-                        .branches
-                        .iter()
-                        .filter(|b| {
-                            from_branch
-                                .filter
-                                .should_include_branch(b, self.last_known_id)
-                        })
-                        .map(|b| (b.pos, b.dir))
-                        .collect();
-
-                    for (pos, dir) in origins {
-                        let shape_centre = pos + from_branch.offset.offset_dir(dir);
-                        spawn_attractor.shape.generate(
-                            shape_centre,
-                            &mut self.attractors,
-                            &spawn_attractor.attractor_spacing,
-                            &mut self.rng,
-                        );
-                    }
+                let set = self.attractor_set_index(&spawn_attractor.set);
+                for attractor in &mut self.attractors[start..] {
+                    attractor.set = set;
                 }
-            },
+            }
+            ShrubberyStep::RemoveAttractors(remove) => {
+                self.remove_attractors(remove);
+            }
             ShrubberyStep::Grow(grow_trunk) => {
                 self.grow_direction(grow_trunk);
             }
@@ -541,6 +950,131 @@ impl ShrubberyGenerator {
             ShrubberyStep::ClearAttractors => {
                 self.attractors.clear();
             }
+            ShrubberyStep::ClearAttractorSet(name) => {
+                if let Some(set) = self.attractor_sets.iter().position(|set| set == name) {
+                    self.attractors.retain(|attractor| attractor.set != set);
+                }
+            }
+            ShrubberyStep::ComputeThickness(compute_thickness) => {
+                self.compute_thickness(compute_thickness);
+            }
+        }
+        self.step_index += 1;
+    }
+
+    fn compute_thickness(&mut self, compute_thickness: &ComputeThickness) {
+        let exponent = compute_thickness.exponent.max(f32::EPSILON);
+        let max_radius = compute_thickness.max_radius.max(0.0);
+        let tip_radius = compute_thickness.tip_radius.clamp(0.0, max_radius);
+
+        // parents always precede their children, so a reverse walk visits
+        // every child before its parent
+        let mut child_sums = vec![0.0_f32; self.branches.len()];
+        let mut child_counts = vec![0_u32; self.branches.len()];
+        for branch_index in (0..self.branches.len()).rev() {
+            let radius = if child_counts[branch_index] == 0 {
+                tip_radius
+            } else {
+                child_sums[branch_index]
+                    .powf(exponent.recip())
+                    .min(max_radius)
+            };
+            self.branches[branch_index].thickness = radius;
+            if let Some(parent_index) = self.branches[branch_index].parent_index
+                && parent_index < branch_index
+            {
+                child_sums[parent_index] += radius.powf(exponent);
+                child_counts[parent_index] += 1;
+            }
+        }
+
+        for branch_index in 0..self.branches.len() {
+            let branch = &self.branches[branch_index];
+            // an only child continuing from the parent's end carries on its radius
+            let start_thickness = match branch.parent_index {
+                Some(parent_index)
+                    if child_counts[parent_index] == 1
+                        && branch.start_pos == self.branches[parent_index].pos =>
+                {
+                    self.branches[parent_index].thickness
+                }
+                _ => branch.thickness,
+            };
+            let (pos, thickness) = (branch.pos, branch.thickness);
+            self.branches[branch_index].start_thickness = start_thickness;
+            self.update_bound(pos, thickness.max(start_thickness));
+        }
+    }
+
+    fn spawn_attractors(&mut self, spawn_attractor: &SpawnAttractors) {
+        for shape_centre in self.shape_centres(&spawn_attractor.location) {
+            spawn_attractor.generate(shape_centre, &mut self.attractors, &mut self.rng);
+        }
+    }
+
+    /// Where `location` places an attractor shape, one centre per selected branch.
+    fn shape_centres(&self, location: &SpawnAttractorLocation) -> Vec<Vec3> {
+        match location {
+            SpawnAttractorLocation::Pos(pos) => vec![*pos],
+            SpawnAttractorLocation::FromBranch(from_branch) => self
+                .branch_indices_filtered_vec(&from_branch.filter)
+                .into_iter()
+                .map(|i| {
+                    let branch = &self.branches[i];
+                    branch.pos + from_branch.offset.offset_dir(branch.dir)
+                })
+                .collect(),
+        }
+    }
+
+    fn remove_attractors(&mut self, remove: &RemoveAttractors) {
+        let shape_centres = self.shape_centres(&remove.location);
+        let sets: Vec<usize> = remove
+            .sets
+            .iter()
+            .filter_map(|name| self.attractor_sets.iter().position(|set| set == name))
+            .collect();
+        self.attractors.retain(|attractor| {
+            let in_sets = remove.sets.is_empty() || sets.contains(&attractor.set);
+            !in_sets
+                || !shape_centres
+                    .iter()
+                    .any(|centre| remove.shape.contains(attractor.pos - centre))
+        });
+    }
+
+    /// Index the grown branch segments for [`BranchVolumes::contains`] queries.
+    pub fn branch_volumes(&self) -> BranchVolumes<'_> {
+        let max_radius = self
+            .branches
+            .iter()
+            .map(|branch| branch.thickness.max(branch.start_thickness))
+            .filter(|radius| radius.is_finite())
+            .fold(0.0, f32::max);
+        // segments are sampled every `cell_size`, so a point inside one is at most
+        // `max_radius + cell_size / 2` from a sample, always in a neighbouring cell
+        let cell_size = (2.0 * max_radius).max(1.0);
+        let mut grid = SpatialGrid::new(cell_size);
+        // roots have no segment
+        for (index, branch) in self.branches.iter().enumerate() {
+            if branch.parent_index.is_none() {
+                continue;
+            }
+            let samples = (branch.start_pos.distance(branch.pos) / cell_size)
+                .ceil()
+                .max(1.0) as usize;
+            for sample in 0..=samples {
+                grid.insert(
+                    index,
+                    branch
+                        .start_pos
+                        .lerp(branch.pos, sample as f32 / samples as f32),
+                );
+            }
+        }
+        BranchVolumes {
+            branches: &self.branches,
+            grid,
         }
     }
 
@@ -563,18 +1097,13 @@ impl ShrubberyGenerator {
         let group_index = self.leaf_groups.len();
         self.leaf_groups
             .push((step.shape.clone(), step.voxel.clone()));
+        self.leaf_group_steps.push(self.step_index);
 
         // Roots are skipped: with IterationFilter::Last they'd register as the
         // last iteration and get decorated at the tree base.
-        for branch in self
-            .branches
-            .iter_mut()
-            .filter(|branch| branch.parent_index.is_some())
-        {
-            let qualifies = step
-                .filter
-                .should_include_branch(branch, self.last_known_id);
-            if !qualifies {
+        for branch_index in self.branch_indices_filtered_vec(&step.filter) {
+            let branch = &mut self.branches[branch_index];
+            if branch.parent_index.is_none() {
                 continue;
             }
             if branch.leaf_group.is_none() || step.overwrite {
@@ -627,24 +1156,45 @@ impl ShrubberyGenerator {
 
         let decoration_index = self.branch_decorations.len();
         self.branch_decorations.push(grow_trunk.voxel.clone());
+        self.branch_decoration_steps.push(self.step_index);
+        self.branch_curves.push(grow_trunk.curve.clone());
         let times = grow_trunk.times.get(&mut self.rng);
 
         let id = grow_trunk.id.get(self.last_known_id);
+        let tags = BranchTag::mask_of(&grow_trunk.tags);
 
         match grow_trunk.dir.attractors() {
             Some(attractor_settings) => {
-                let mut active_branches = self.branch_indices_filtered_hashset(&grow_trunk.filter);
+                // unknown set names have no attractors, so they consume nothing
+                let consumed_sets: Vec<usize> = attractor_settings
+                    .sets
+                    .iter()
+                    .filter_map(|name| self.attractor_sets.iter().position(|set| set == name))
+                    .collect();
+                let consumes =
+                    |set: usize| attractor_settings.sets.is_empty() || consumed_sets.contains(&set);
+                let mut active_branches = self.branch_indices_filtered_vec(&grow_trunk.filter);
+                let min_attractors = attractor_settings.min_attractors.max(1) as i32;
+                let perception_cos = attractor_settings.perception_angle.to_radians().cos();
+                let mut branch_grid = SpatialGrid::new(
+                    attractor_settings
+                        .kill_distance
+                        .max(attractor_settings.attract_distance),
+                );
                 for i in 0..times {
-                    for attractor in self.attractors.iter_mut() {
+                    branch_grid.clear();
+                    for &branch_index in active_branches.iter() {
+                        branch_grid.insert(branch_index, self.branches[branch_index].pos);
+                    }
+                    for attractor in self
+                        .attractors
+                        .iter_mut()
+                        .filter(|attractor| consumes(attractor.set))
+                    {
                         let mut closest_branch: Option<usize> = None;
                         let mut closest_dist = 999999.;
-                        for (branch_index, branch) in self
-                            .branches
-                            .iter_mut()
-                            .enumerate()
-                            .filter(|(i, _branch)| active_branches.contains(i))
-                        {
-                            let dist = attractor.pos.distance(branch.pos);
+                        for branch_index in branch_grid.neighbours(attractor.pos) {
+                            let dist = attractor.pos.distance(self.branches[branch_index].pos);
                             if dist < attractor_settings.kill_distance {
                                 attractor.reached = true;
                                 closest_branch = None;
@@ -653,7 +1203,19 @@ impl ShrubberyGenerator {
                             if dist > attractor_settings.attract_distance {
                                 continue;
                             }
-                            if dist < closest_dist {
+                            if attractor_settings.perception_angle < 180.0 {
+                                let branch = &self.branches[branch_index];
+                                let to_attractor = (attractor.pos - branch.pos).normalize_or_zero();
+                                if branch.original_dir.dot(to_attractor) < perception_cos {
+                                    continue;
+                                }
+                            }
+                            // grid order is arbitrary, ties go to the lowest index
+                            // so the result matches a linear scan
+                            let is_closer = dist < closest_dist
+                                || (dist == closest_dist
+                                    && closest_branch.is_some_and(|c| branch_index < c));
+                            if is_closer {
                                 closest_branch = Some(branch_index);
                                 closest_dist = dist;
                             }
@@ -661,24 +1223,30 @@ impl ShrubberyGenerator {
                         if let Some(closest_branch_index) = closest_branch {
                             let closest_branch_pos = self.branches[closest_branch_index].pos;
                             let new_branch_dir = attractor.pos - closest_branch_pos;
-                            let new_branch_dir = new_branch_dir.normalize();
+                            let new_branch_dir =
+                                new_branch_dir.normalize_or_zero() * attractor.weight;
                             self.branches[closest_branch_index].dir += new_branch_dir;
                             self.branches[closest_branch_index].attractors_count += 1;
                         }
                     }
                     self.attractors.retain(|attractor| !attractor.reached);
 
+                    // only active branches can have been pulled, visited in index order
                     let mut to_add = vec![];
-                    for (branch_index, branch) in self
-                        .branches
-                        .iter_mut()
-                        .enumerate()
-                        .filter(|(_, branch)| branch.attractors_count > 0)
-                    {
+                    for &branch_index in active_branches.iter() {
+                        let branch = &mut self.branches[branch_index];
+                        if branch.attractors_count < min_attractors {
+                            branch.reset();
+                            continue;
+                        }
                         let mut branch_rng =
                             rand_chacha::ChaCha8Rng::seed_from_u64(branch_index as u64);
                         let thickness = grow_trunk.thickness.get(i, times, &mut branch_rng);
-                        branch.dir = branch.dir.normalize();
+                        // opposing pulls can cancel out, keep growing the old way then
+                        branch.dir = branch.dir.normalize_or(branch.original_dir);
+                        if let Some(tropism) = &attractor_settings.tropism {
+                            branch.dir = tropism.apply(branch.dir, i, times);
+                        }
 
                         let (iteration_value, iteration_max) =
                             match attractor_settings.iteration_calculation {
@@ -698,7 +1266,10 @@ impl ShrubberyGenerator {
                             iteration_max,
                         );
                         self.last_known_id = u32::max(self.last_known_id, new_branch.id);
+                        new_branch.start_thickness =
+                            grow_trunk.thickness.start(i, branch.thickness, thickness);
                         new_branch.decoration_group = Some(decoration_index);
+                        new_branch.tags = tags;
                         branch.child_count += 1;
                         to_add.push(new_branch);
                         branch.reset();
@@ -708,11 +1279,17 @@ impl ShrubberyGenerator {
                     for branch in &to_add {
                         self.update_bound(branch.pos, branch.thickness);
                     }
+                    // nothing reachable grew, later iterations would see the same state
+                    if to_add.is_empty() {
+                        break;
+                    }
                     let branch_len = self.branches.len();
                     self.branches.extend(to_add);
-                    // Only newly-spawned branches are eligible to grow next round;
-                    // their parents already extended this iteration.
-                    active_branches.clear();
+                    if attractor_settings.growth == AttractorGrowth::Tips {
+                        // Only newly-spawned branches are eligible to grow next round;
+                        // their parents already extended this iteration.
+                        active_branches.clear();
+                    }
                     active_branches.extend(branch_len..self.branches.len());
                 }
             }
@@ -730,11 +1307,44 @@ impl ShrubberyGenerator {
                 BranchSpawnMethod::GrowRadial(grow_radial) => {
                     self.grow_radial(grow_trunk, grow_radial, decoration_index, times, id);
                 }
+                BranchSpawnMethod::GrowLateral(grow_lateral) => {
+                    self.grow_lateral(grow_trunk, grow_lateral, decoration_index, times, id);
+                }
+                BranchSpawnMethod::Phyllotaxis(phyllotaxis) => {
+                    self.grow_phyllotaxis(grow_trunk, phyllotaxis, decoration_index, times, id);
+                }
+                BranchSpawnMethod::Fork(fork) => {
+                    self.grow_fork(grow_trunk, fork, decoration_index, times, id);
+                }
             },
         }
         self.last_known_id = id;
     }
 
+    /// [`BranchGrowthDirection::Wander`] resolved to a target for a segment grown
+    /// from `parent_index`, `None` for other directions.
+    fn wander_dir(
+        &self,
+        dir: &BranchGrowthDirection,
+        parent_index: usize,
+        noise_seed: u64,
+    ) -> Option<BranchGrowthDirection> {
+        let BranchGrowthDirection::Wander {
+            amplitude,
+            frequency,
+        } = *dir
+        else {
+            return None;
+        };
+        let parent = &self.branches[parent_index];
+        Some(BranchGrowthDirection::Target(noise::wander(
+            noise_seed,
+            parent.pos * frequency,
+            parent.dir,
+            amplitude,
+        )))
+    }
+
     fn grow_branches_from_indices(
         &mut self,
         grow_step: &GrowStep,
@@ -743,22 +1353,31 @@ impl ShrubberyGenerator {
         indices: Vec<usize>,
         id: u32,
     ) {
+        let tags = BranchTag::mask_of(&grow_step.tags);
         for branch_index in indices.iter() {
             let mut running_index = *branch_index;
+            // every chain gets its own noise field, so siblings twist apart
+            let noise_seed = noise::mix(self.seed ^ noise::mix(*branch_index as u64));
             for i in 0..grow_times {
                 let thickness = grow_step.thickness.get(i, grow_times, &mut self.rng);
 
+                let wandered = self.wander_dir(&grow_step.dir, running_index, noise_seed);
                 let mut new_branch = self.branches[running_index].child(
                     running_index,
                     grow_step.length.get(&mut self.rng),
                     id,
-                    &grow_step.dir,
+                    wandered.as_ref().unwrap_or(&grow_step.dir),
                     thickness,
                     i,
                     grow_times,
                 );
+                new_branch.start_thickness =
+                    grow_step
+                        .thickness
+                        .start(i, self.branches[running_index].thickness, thickness);
                 new_branch.decoration_group = Some(decoration_index);
-                self.branches[*branch_index].child_count += 1;
+                new_branch.tags = tags;
+                self.branches[running_index].child_count += 1;
                 self.update_bound(new_branch.pos, thickness);
                 self.branches.push(new_branch);
                 running_index = self.branches.len() - 1;
@@ -776,6 +1395,7 @@ impl ShrubberyGenerator {
         // let mut active_branches = self.branch_indices_filtered_hashset(&grow_step.filter);
         let mut active_branches = self.branch_indices_filtered_vec(&grow_step.filter);
         // for branch_index in active_branches.iter() {
+        let tags = BranchTag::mask_of(&grow_step.tags);
         let new_branch_count = grow_radial.count.get(&mut self.rng);
         if new_branch_count == 0 {
             // continue;
@@ -794,9 +1414,8 @@ impl ShrubberyGenerator {
         let mut to_add = vec![];
         for branch_index in active_branches.iter() {
             for i in 0..new_branch_count {
-                let degree_jitter = self
-                    .rng
-                    .random_range(-allowed_degree_range..=allowed_degree_range);
+                let degree_jitter =
+                    random_range_f32(&mut self.rng, -allowed_degree_range, allowed_degree_range);
 
                 let yaw =
                     (rotation_offset + degrees_per_segment * i as f32 + degree_jitter).to_radians();
@@ -818,6 +1437,7 @@ impl ShrubberyGenerator {
                 );
 
                 new_branch.decoration_group = Some(decoration_index);
+                new_branch.tags = tags;
                 self.branches[*branch_index].child_count += 1;
 
                 self.update_bound(new_branch.pos, thickness);
@@ -844,12 +1464,239 @@ impl ShrubberyGenerator {
         }
     }
 
+    pub fn grow_lateral(
+        &mut self,
+        grow_step: &GrowStep,
+        grow_lateral: &GrowLateral,
+        decoration_index: usize,
+        times: u32,
+        id: u32,
+    ) {
+        if times == 0 {
+            return;
+        }
+        let tags = BranchTag::mask_of(&grow_step.tags);
+        let hosts = self.branch_indices_filtered_vec(&grow_step.filter);
+
+        let mut laterals = vec![];
+        for host_index in hosts {
+            // the root has no segment to sprout from
+            if self.branches[host_index].parent_index.is_none() {
+                continue;
+            }
+            let host = &self.branches[host_index];
+            let segment = host.pos - host.start_pos;
+            let Some(segment_dir) = segment.try_normalize() else {
+                continue;
+            };
+            let count = (segment.length() * grow_lateral.density.max(0.0)).floor() as u32;
+            let (host_start_pos, host_start_thickness, host_thickness) =
+                (host.start_pos, host.start_thickness, host.thickness);
+
+            for i in 0..count {
+                let t = match grow_lateral.placement {
+                    LateralPlacement::Even => (i as f32 + 0.5) / count as f32,
+                    LateralPlacement::Random => random_range_f32(&mut self.rng, 0.0, 1.0),
+                };
+                let interpolated_thickness =
+                    host_start_thickness + (host_thickness - host_start_thickness) * t;
+
+                let roll = random_range_f32(&mut self.rng, 0.0, 360.0).to_radians();
+                let pitch = grow_lateral.pitch_degrees.get(&mut self.rng).to_radians();
+                let side =
+                    Quat::from_axis_angle(segment_dir, roll) * segment_dir.any_orthonormal_vector();
+                let dir = segment_dir * pitch.cos() + side * pitch.sin();
+
+                let thickness = grow_step
+                    .thickness
+                    .get(i, count, &mut self.rng)
+                    .min(interpolated_thickness);
+                let mut new_branch = self.branches[host_index].child(
+                    host_index,
+                    grow_step.length.get(&mut self.rng),
+                    id,
+                    &BranchGrowthDirection::Target(dir),
+                    thickness,
+                    i,
+                    count,
+                );
+                // move the segment from the host's end to its sprouting point
+                let start_pos = host_start_pos + segment * t;
+                new_branch.pos += start_pos - new_branch.start_pos;
+                new_branch.start_pos = start_pos;
+                new_branch.sprout_from(interpolated_thickness);
+                new_branch.decoration_group = Some(decoration_index);
+                new_branch.tags = tags;
+
+                self.branches[host_index].child_count += 1;
+                self.update_bound(new_branch.pos, thickness);
+                laterals.push(self.branches.len());
+                self.branches.push(new_branch);
+            }
+        }
+
+        // continue growing, from the newly created side branches
+        if times > 1 {
+            self.grow_branches_from_indices(grow_step, decoration_index, times - 1, laterals, id);
+        }
+    }
+
+    pub fn grow_phyllotaxis(
+        &mut self,
+        grow_step: &GrowStep,
+        phyllotaxis: &Phyllotaxis,
+        decoration_index: usize,
+        times: u32,
+        id: u32,
+    ) {
+        let count = phyllotaxis.arrangement.count();
+        if times == 0 || count == 0 {
+            return;
+        }
+        let tags = BranchTag::mask_of(&grow_step.tags);
+        let divergence = phyllotaxis
+            .divergence_degrees
+            .unwrap_or_else(|| phyllotaxis.arrangement.divergence_degrees());
+        let spacing = 360.0 / count as f32;
+        // randomize base direction
+        let rotation_offset = random_range_f32(&mut self.rng, 0.0, 360.0);
+
+        let mut to_add = vec![];
+        for node_index in self.branch_indices_filtered_vec(&grow_step.filter) {
+            let node = &self.branches[node_index];
+            let node_dir = node.dir;
+            // a stable reference around the node's direction, so the pattern
+            // keeps its phase along a bending chain
+            let reference = (Vec3::X - node_dir * node_dir.dot(Vec3::X))
+                .try_normalize()
+                .unwrap_or_else(|| node_dir.any_orthonormal_vector());
+            let node_phase = rotation_offset + divergence * self.depth(node_index) as f32;
+
+            for i in 0..count {
+                let yaw = (node_phase + spacing * i as f32).to_radians();
+                let side = Quat::from_axis_angle(node_dir, yaw) * reference;
+                let angle = phyllotaxis
+                    .branch_angle_degrees
+                    .get(&mut self.rng)
+                    .to_radians();
+                let dir = node_dir * angle.cos() + side * angle.sin();
+
+                let thickness = grow_step.thickness.get(i, count, &mut self.rng);
+                let mut new_branch = self.branches[node_index].child(
+                    node_index,
+                    grow_step.length.get(&mut self.rng),
+                    id,
+                    &BranchGrowthDirection::Target(dir),
+                    thickness,
+                    i,
+                    count,
+                );
+                // sprouts from the node's end, as thick as the node is there
+                new_branch.sprout_from(self.branches[node_index].thickness);
+                new_branch.decoration_group = Some(decoration_index);
+                new_branch.tags = tags;
+                self.branches[node_index].child_count += 1;
+                self.update_bound(new_branch.pos, thickness);
+                to_add.push(new_branch);
+            }
+        }
+        let branch_len = self.branches.len();
+        self.branches.extend(to_add);
+
+        // continue growing, from the newly created branches
+        if times > 1 {
+            self.grow_branches_from_indices(
+                grow_step,
+                decoration_index,
+                times - 1,
+                (branch_len..self.branches.len()).collect(),
+                id,
+            );
+        }
+    }
+
+    pub fn grow_fork(
+        &mut self,
+        grow_step: &GrowStep,
+        fork: &Fork,
+        decoration_index: usize,
+        times: u32,
+        id: u32,
+    ) {
+        let tags = BranchTag::mask_of(&grow_step.tags);
+        // (tip, noise seed), each chain wanders through its own noise field
+        let mut active_branches: Vec<(usize, u64)> = self
+            .branch_indices_filtered_vec(&grow_step.filter)
+            .into_iter()
+            .map(|index| (index, noise::mix(self.seed ^ noise::mix(index as u64))))
+            .collect();
+        for i in 0..times {
+            let mut to_add = vec![];
+            let mut noise_seeds = vec![];
+            for &(tip_index, noise_seed) in active_branches.iter() {
+                let tip_dir = self.branches[tip_index].dir;
+                let forks = self
+                    .rng
+                    .random_bool((fork.chance / 100.0).clamp(0.0, 1.0) as f64);
+                let count = if forks {
+                    fork.count.get(&mut self.rng)
+                } else {
+                    1
+                };
+                let roll_offset = random_range_f32(&mut self.rng, 0.0, 360.0);
+                let reference = tip_dir.any_orthonormal_vector();
+                for c in 0..count {
+                    let dir = if forks {
+                        let roll = (roll_offset + 360.0 * c as f32 / count as f32).to_radians();
+                        let side = Quat::from_axis_angle(tip_dir, roll) * reference;
+                        let spread = fork.spread_degrees.get(&mut self.rng).to_radians();
+                        BranchGrowthDirection::Target(tip_dir * spread.cos() + side * spread.sin())
+                    } else {
+                        self.wander_dir(&grow_step.dir, tip_index, noise_seed)
+                            .unwrap_or_else(|| grow_step.dir.clone())
+                    };
+                    // siblings of a fork twist apart, a single continuation keeps its field
+                    noise_seeds.push(if forks {
+                        noise::mix(noise_seed ^ noise::mix(c as u64))
+                    } else {
+                        noise_seed
+                    });
+                    let thickness = grow_step.thickness.get(i, times, &mut self.rng);
+                    let mut new_branch = self.branches[tip_index].child(
+                        tip_index,
+                        grow_step.length.get(&mut self.rng),
+                        id,
+                        &dir,
+                        thickness,
+                        i,
+                        times,
+                    );
+                    new_branch.start_thickness =
+                        grow_step
+                            .thickness
+                            .start(i, self.branches[tip_index].thickness, thickness);
+                    new_branch.decoration_group = Some(decoration_index);
+                    new_branch.tags = tags;
+                    self.branches[tip_index].child_count += 1;
+                    self.update_bound(new_branch.pos, thickness);
+                    to_add.push(new_branch);
+                }
+            }
+            let branch_len = self.branches.len();
+            self.branches.extend(to_add);
+            // the new tips fork next iteration
+            active_branches.clear();
+            active_branches.extend((branch_len..self.branches.len()).zip(noise_seeds));
+        }
+    }
+
     fn spawn_root_branch(&mut self, spawn_root_branch: &SpawnRootStep) {
         let times = spawn_root_branch.times.get(&mut self.rng);
         for i in 0..times {
             let dir = spawn_root_branch.initial_dir.get(&mut self.rng);
             let root = Branch {
                 pos: spawn_root_branch.pos,
+                start_pos: spawn_root_branch.pos,
                 parent_index: None,
                 dir,
                 attractors_count: 0,
@@ -858,9 +1705,11 @@ impl ShrubberyGenerator {
                 iteration: i,
                 leaf_group: None,
                 thickness: 1.0,
+                start_thickness: 1.0,
                 decoration_group: None,
                 iteration_total: times,
                 id: spawn_root_branch.id.get(self.last_known_id),
+                tags: BranchTag::mask_of(&spawn_root_branch.tags),
             };
             self.last_known_id = root.id;
             self.branches.push(root);
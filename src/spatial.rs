@@ -0,0 +1,46 @@
+//! uniform grid used to accelerate neighbour queries during generation
+use ahash::HashMap;
+use glam::{IVec3, Vec3};
+
+/// Buckets indices by position into cubic cells of `cell_size`.
+///
+/// A query visits the 3x3x3 block of cells around a position, so every entry
+/// within `cell_size` of it is returned (plus some further away, which the
+/// caller is expected to distance test).
+pub(crate) struct SpatialGrid {
+    cell_size: f32,
+    cells: HashMap<IVec3, Vec<usize>>,
+}
+
+impl SpatialGrid {
+    pub fn new(cell_size: f32) -> Self {
+        Self {
+            cell_size: cell_size.max(0.001),
+            cells: HashMap::default(),
+        }
+    }
+
+    fn cell(&self, pos: Vec3) -> IVec3 {
+        (pos / self.cell_size).floor().as_ivec3()
+    }
+
+    pub fn insert(&mut self, index: usize, pos: Vec3) {
+        let cell = self.cell(pos);
+        self.cells.entry(cell).or_default().push(index);
+    }
+
+    /// Empties all cells, keeping their allocations for the next rebuild.
+    pub fn clear(&mut self) {
+        self.cells.values_mut().for_each(Vec::clear);
+    }
+
+    /// Candidate indices near `pos`, in no particular order.
+    pub fn neighbours(&self, pos: Vec3) -> impl Iterator<Item = usize> + '_ {
+        let center = self.cell(pos);
+        (-1..=1)
+            .flat_map(|x| (-1..=1).flat_map(move |y| (-1..=1).map(move |z| IVec3::new(x, y, z))))
+            .filter_map(move |offset| self.cells.get(&center.wrapping_add(offset)))
+            .flatten()
+            .copied()
+    }
+}
@@ -0,0 +1,137 @@
+//! named branch tags, an alternative to numeric branch ids
+//! names are resolved to bits of a `u64` mask once, so filtering stays a bitwise and
+use ahash::HashMap;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::{
+    filter::{Filter, FilterExpr, IdFilter},
+    shrubbery::{ShrubberySettings, ShrubberyStep, SpawnAttractorLocation},
+};
+
+/// Most distinct tag names a single [`ShrubberySettings`] can use.
+pub const MAX_BRANCH_TAGS: usize = u64::BITS as usize;
+
+/// A named label steps assign to the branches they create, written as a plain string in RON.
+/// [`ShrubberySettings::resolve_tags`] maps each name to a bit of [`Branch::tags`](crate::branch::Branch::tags).
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(from = "String", into = "String")
+)]
+pub struct BranchTag {
+    pub name: String,
+    /// Single bit mask, `0` until resolved.
+    pub mask: u64,
+}
+
+impl BranchTag {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            mask: 0,
+        }
+    }
+
+    /// Combined mask of `tags`.
+    pub fn mask_of(tags: &[BranchTag]) -> u64 {
+        tags.iter().fold(0, |mask, tag| mask | tag.mask)
+    }
+}
+
+impl From<String> for BranchTag {
+    fn from(name: String) -> Self {
+        Self::new(name)
+    }
+}
+
+impl From<BranchTag> for String {
+    fn from(tag: BranchTag) -> Self {
+        tag.name
+    }
+}
+
+impl ShrubberySettings {
+    /// Assign every distinct tag name a bit, in order of first appearance.
+    /// Runs automatically when deserializing, call it after building settings in code.
+    /// Names past [`MAX_BRANCH_TAGS`] stay unresolved and match no branches.
+    pub fn resolve_tags(&mut self) {
+        let mut bits = HashMap::<String, u64>::default();
+        for tag in self.tags_mut() {
+            let next_bit = bits.len();
+            tag.mask = match bits.get(&tag.name) {
+                Some(mask) => *mask,
+                None if next_bit < MAX_BRANCH_TAGS => {
+                    bits.insert(tag.name.clone(), 1 << next_bit);
+                    1 << next_bit
+                }
+                None => 0,
+            };
+        }
+    }
+
+    /// Every tag in the build steps, both assigned and filtered on.
+    pub fn tags_mut(&mut self) -> impl Iterator<Item = &mut BranchTag> {
+        self.build_steps.iter_mut().flat_map(|step| {
+            let mut tags: Vec<&mut BranchTag> = Vec::new();
+            match step {
+                ShrubberyStep::SpawnRoot(spawn_root) => tags.extend(spawn_root.tags.iter_mut()),
+                ShrubberyStep::Grow(grow) => {
+                    tags.extend(grow.tags.iter_mut());
+                    filter_tags_mut(&mut grow.filter, &mut tags);
+                }
+                ShrubberyStep::SpawnAttractors(spawn) => {
+                    if let SpawnAttractorLocation::FromBranch(from_branch) = &mut spawn.location {
+                        filter_tags_mut(&mut from_branch.filter, &mut tags);
+                    }
+                }
+                ShrubberyStep::RemoveAttractors(remove) => {
+                    if let SpawnAttractorLocation::FromBranch(from_branch) = &mut remove.location {
+                        filter_tags_mut(&mut from_branch.filter, &mut tags);
+                    }
+                }
+                ShrubberyStep::Shape(shape) => filter_tags_mut(&mut shape.filter, &mut tags),
+                ShrubberyStep::ClearAttractors
+                | ShrubberyStep::ClearAttractorSet(_)
+                | ShrubberyStep::ComputeThickness(_) => (),
+            }
+            tags
+        })
+    }
+}
+
+fn filter_tags_mut<'a>(filter: &'a mut Filter, tags: &mut Vec<&'a mut BranchTag>) {
+    if let IdFilter::Tag(tag) = &mut filter.id {
+        tags.push(tag);
+    }
+    expr_tags_mut(&mut filter.expr, tags);
+}
+
+fn expr_tags_mut<'a>(expr: &'a mut FilterExpr, tags: &mut Vec<&'a mut BranchTag>) {
+    match expr {
+        FilterExpr::All(exprs) | FilterExpr::Any(exprs) => {
+            for expr in exprs {
+                expr_tags_mut(expr, tags);
+            }
+        }
+        FilterExpr::Not(expr) => expr_tags_mut(expr, tags),
+        FilterExpr::Id(IdFilter::Tag(tag)) => tags.push(tag),
+        _ => (),
+    }
+}
+
+/// `deserialize_with` for [`ShrubberySettings::build_steps`], resolves tags while loading.
+#[cfg(feature = "serde")]
+pub(crate) fn deserialize_build_steps<'de, D>(
+    deserializer: D,
+) -> Result<Vec<ShrubberyStep>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let mut settings = ShrubberySettings {
+        build_steps: Vec::deserialize(deserializer)?,
+    };
+    settings.resolve_tags();
+    Ok(settings.build_steps)
+}
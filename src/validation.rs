@@ -0,0 +1,588 @@
+//! static checks for [`ShrubberySettings`]
+//! catches settings that would silently generate nothing, or the wrong thing
+use std::fmt;
+
+use ahash::HashSet;
+
+use crate::{
+    filter::{Filter, FilterExpr, IdFilter},
+    shrubbery::{
+        AssignBranchId, BranchGrowthDirection, BranchSpawnMethod, BranchThickness, InitialDir,
+        ShrubberySettings, ShrubberyStep, SpawnAttractorLocation, StepChance, Tropism,
+        TropismStrength,
+    },
+    tag::{BranchTag, MAX_BRANCH_TAGS},
+    value_or_range::{ValueOrRangeF32, ValueOrRangeU32},
+    voxel::{DecorationSelector, LeafDecoration, Shape},
+};
+
+/// How bad a [`Diagnostic`] is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// Generation works, but likely not as intended.
+    Warning,
+    /// The step can't do anything useful, or generation may fail.
+    Error,
+}
+
+/// A single problem found by [`ShrubberySettings::validate`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+    /// Index into [`ShrubberySettings::build_steps`].
+    pub step_index: usize,
+    /// Path to the offending field inside the step, e.g. `thickness.min`.
+    pub field: String,
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:?}: build_steps[{}].{}: {}",
+            self.severity, self.step_index, self.field, self.message
+        )
+    }
+}
+
+impl ShrubberySettings {
+    /// Check the build steps for mistakes that generation would otherwise
+    /// silently clamp, skip or ignore. An empty list means no problems were found.
+    pub fn validate(&self) -> Vec<Diagnostic> {
+        let mut validator = Validator::default();
+        for (step_index, step) in self.build_steps.iter().enumerate() {
+            validator.step_index = step_index;
+            validator.step(step);
+        }
+        validator.diagnostics
+    }
+}
+
+#[derive(Default)]
+struct Validator {
+    step_index: usize,
+    diagnostics: Vec<Diagnostic>,
+    /// Ids that an earlier step may have assigned, mirrors `ShrubberyGenerator::last_known_id`.
+    assigned_ids: HashSet<u32>,
+    last_known_id: u32,
+    /// Tag names assigned by earlier steps.
+    assigned_tags: HashSet<String>,
+    /// Attractor set names spawned into by earlier steps.
+    attractor_sets: HashSet<String>,
+}
+
+impl Validator {
+    fn push(&mut self, severity: Severity, field: &str, message: String) {
+        self.diagnostics.push(Diagnostic {
+            step_index: self.step_index,
+            field: field.to_string(),
+            severity,
+            message,
+        });
+    }
+
+    fn step(&mut self, step: &ShrubberyStep) {
+        match step {
+            ShrubberyStep::SpawnRoot(spawn_root) => {
+                self.range_u32("times", &spawn_root.times);
+                if let InitialDir::Random {
+                    y_rotation_range,
+                    z_rotation_max,
+                } = &spawn_root.initial_dir
+                {
+                    self.range_u32("initial_dir.y_rotation_range", y_rotation_range);
+                    self.range_u32("initial_dir.z_rotation_max", z_rotation_max);
+                }
+                // AutoIncrement hands every root its own id
+                for _ in 0..spawn_root.times.max().min(u16::MAX as u32) {
+                    self.assign_id(&spawn_root.id);
+                }
+                self.assign_tags("tags", &spawn_root.tags);
+            }
+            ShrubberyStep::Grow(grow) => {
+                self.chance("chance", &grow.chance);
+                // filters see the ids assigned before this step
+                self.filter("filter", &grow.filter);
+                self.range_u32("times", &grow.times);
+                self.range_f32("length", &grow.length);
+                match &grow.thickness {
+                    BranchThickness::ValueOrRange(value) => self.range_f32("thickness", value),
+                    BranchThickness::IterationScale { min, max } => {
+                        self.range_f32("thickness.min", min);
+                        self.range_f32("thickness.max", max);
+                    }
+                }
+                if let BranchSpawnMethod::GrowLateral(lateral) = &grow.spawn_method {
+                    if lateral.density <= 0.0 {
+                        self.push(
+                            Severity::Warning,
+                            "spawn_method.density",
+                            format!("{} spawns no side branches", lateral.density),
+                        );
+                    }
+                    self.range_f32("spawn_method.pitch_degrees", &lateral.pitch_degrees);
+                }
+                if let BranchSpawnMethod::Phyllotaxis(phyllotaxis) = &grow.spawn_method {
+                    if phyllotaxis.arrangement.count() == 0 {
+                        self.push(
+                            Severity::Warning,
+                            "spawn_method.arrangement.count",
+                            "a whorl of 0 spawns no branches".to_string(),
+                        );
+                    }
+                    self.range_f32(
+                        "spawn_method.branch_angle_degrees",
+                        &phyllotaxis.branch_angle_degrees,
+                    );
+                }
+                if let BranchSpawnMethod::Fork(fork) = &grow.spawn_method {
+                    self.range_u32("spawn_method.count", &fork.count);
+                    match fork.count {
+                        ValueOrRangeU32::Value(0) => self.push(
+                            Severity::Warning,
+                            "spawn_method.count",
+                            "a fork of 0 ends every forking chain".to_string(),
+                        ),
+                        ValueOrRangeU32::Range(0, _) => self.push(
+                            Severity::Warning,
+                            "spawn_method.count",
+                            "range min is 0, some forks end their chain".to_string(),
+                        ),
+                        _ => {}
+                    }
+                    self.range_f32("spawn_method.spread_degrees", &fork.spread_degrees);
+                    if !(0.0..=100.0).contains(&fork.chance) {
+                        self.push(
+                            Severity::Error,
+                            "spawn_method.chance",
+                            format!("{} is outside 0.0..=100.0", fork.chance),
+                        );
+                    }
+                }
+                if let BranchSpawnMethod::GrowRadial(radial) = &grow.spawn_method {
+                    self.range_u32("spawn_method.count", &radial.count);
+                    self.range_f32("spawn_method.pitch_degrees", &radial.pitch_degrees);
+                    if !(0.0..=1.0).contains(&radial.spacing_jitter) {
+                        self.push(
+                            Severity::Error,
+                            "spawn_method.spacing_jitter",
+                            format!("{} is outside 0.0..=1.0", radial.spacing_jitter),
+                        );
+                    }
+                }
+                if let BranchGrowthDirection::Attractor(attractor) = &grow.dir
+                    && attractor.kill_distance >= attractor.attract_distance
+                {
+                    self.push(
+                        Severity::Error,
+                        "dir.kill_distance",
+                        format!(
+                            "kill_distance ({}) >= attract_distance ({}), attractors die before pulling any branch",
+                            attractor.kill_distance, attractor.attract_distance
+                        ),
+                    );
+                }
+                if let BranchGrowthDirection::Tropism(tropism) = &grow.dir {
+                    self.tropism("dir", tropism);
+                }
+                if let BranchGrowthDirection::SteerToward { dir, .. } = grow.dir
+                    && dir.length_squared() == 0.0
+                {
+                    self.push(
+                        Severity::Warning,
+                        "dir.dir",
+                        "zero vector has no direction, branches grow straight".to_string(),
+                    );
+                }
+                if let BranchGrowthDirection::Wander { frequency, .. } = grow.dir
+                    && frequency <= 0.0
+                {
+                    self.push(
+                        Severity::Warning,
+                        "dir.frequency",
+                        format!("{frequency} samples no noise, branches will not wander"),
+                    );
+                }
+                if let BranchGrowthDirection::Attractor(attractor) = &grow.dir {
+                    if let Some(tropism) = &attractor.tropism {
+                        self.tropism("dir.tropism", tropism);
+                    }
+                    if attractor.perception_angle <= 0.0 {
+                        self.push(
+                            Severity::Warning,
+                            "dir.perception_angle",
+                            format!(
+                                "{} degrees perceives no attractors, nothing will grow",
+                                attractor.perception_angle
+                            ),
+                        );
+                    }
+                    for (i, set) in attractor.sets.iter().enumerate() {
+                        if !self.attractor_sets.contains(set) {
+                            self.push(
+                                Severity::Error,
+                                &format!("dir.sets[{i}]"),
+                                format!("no earlier step spawns attractor set \"{set}\""),
+                            );
+                        }
+                    }
+                }
+                self.decoration_selector("voxel", &grow.voxel);
+                self.assign_id(&grow.id);
+                self.assign_tags("tags", &grow.tags);
+            }
+            ShrubberyStep::SpawnAttractors(spawn) => {
+                self.chance("chance", &spawn.chance);
+                if let SpawnAttractorLocation::FromBranch(from_branch) = &spawn.location {
+                    self.filter("location.filter", &from_branch.filter);
+                }
+                if spawn.attractor_spacing.attractor_spacing <= 0.0 {
+                    self.push(
+                        Severity::Error,
+                        "attractor_spacing.attractor_spacing",
+                        format!(
+                            "{} must be positive",
+                            spawn.attractor_spacing.attractor_spacing
+                        ),
+                    );
+                }
+                self.attractor_sets.insert(spawn.set.clone());
+                if !spawn.weight.is_finite() {
+                    self.push(
+                        Severity::Warning,
+                        "weight",
+                        format!("{} is not a finite pull strength", spawn.weight),
+                    );
+                } else if spawn.weight <= 0.0 {
+                    self.push(
+                        Severity::Warning,
+                        "weight",
+                        format!(
+                            "{} weight, the attractors pull no branches toward them",
+                            spawn.weight
+                        ),
+                    );
+                }
+                let (min, max) = spawn.shape.bounds();
+                if (max - min).min_element() <= 0.0 {
+                    self.push(
+                        Severity::Warning,
+                        "shape",
+                        "shape has no volume, no attractors spawn".to_string(),
+                    );
+                }
+            }
+            ShrubberyStep::RemoveAttractors(remove) => {
+                if let SpawnAttractorLocation::FromBranch(from_branch) = &remove.location {
+                    self.filter("location.filter", &from_branch.filter);
+                }
+                for (i, set) in remove.sets.iter().enumerate() {
+                    if !self.attractor_sets.contains(set) {
+                        self.push(
+                            Severity::Warning,
+                            &format!("sets[{i}]"),
+                            format!("no earlier step spawns attractor set \"{set}\""),
+                        );
+                    }
+                }
+            }
+            ShrubberyStep::ClearAttractors => (),
+            ShrubberyStep::ComputeThickness(compute) => {
+                if compute.exponent <= 0.0 {
+                    self.push(
+                        Severity::Error,
+                        "exponent",
+                        format!("{} must be above 0.0", compute.exponent),
+                    );
+                }
+                if compute.tip_radius <= 0.0 {
+                    self.push(
+                        Severity::Warning,
+                        "tip_radius",
+                        format!("{} leaves the tips without bark", compute.tip_radius),
+                    );
+                }
+                if compute.max_radius < compute.tip_radius {
+                    self.push(
+                        Severity::Warning,
+                        "max_radius",
+                        format!(
+                            "max_radius ({}) < tip_radius ({}), every branch gets max_radius",
+                            compute.max_radius, compute.tip_radius
+                        ),
+                    );
+                }
+            }
+            ShrubberyStep::ClearAttractorSet(name) => {
+                if !self.attractor_sets.contains(name) {
+                    self.push(
+                        Severity::Warning,
+                        "set",
+                        format!("no earlier step spawns attractor set \"{name}\""),
+                    );
+                }
+            }
+            ShrubberyStep::Shape(shape) => {
+                self.chance("chance", &shape.chance);
+                self.filter("filter", &shape.filter);
+                match &shape.shape {
+                    Shape::Sphere { radius } => self.range_f32("shape.radius", radius),
+                    Shape::ConiferWhorl(whorl) => {
+                        if whorl.whorl_spacing <= 0.0 {
+                            self.push(
+                                Severity::Warning,
+                                "shape.whorl_spacing",
+                                format!("{} must be positive, clamped to 0.1", whorl.whorl_spacing),
+                            );
+                        }
+                    }
+                    Shape::StarLeaf(star) => self.range_f32("shape.arm_length", &star.arm_length),
+                }
+                self.decoration_selector("voxel", &shape.voxel);
+            }
+        }
+    }
+
+    fn assign_id(&mut self, id: &AssignBranchId) {
+        let id = id.get(self.last_known_id);
+        self.assigned_ids.insert(id);
+        self.last_known_id = id;
+    }
+
+    fn chance(&mut self, field: &str, chance: &StepChance) {
+        if let StepChance::Chance(percent) = chance
+            && !(0.0..=100.0).contains(percent)
+        {
+            self.push(
+                Severity::Error,
+                field,
+                format!("{percent} is outside 0.0..=100.0"),
+            );
+        }
+    }
+
+    fn tropism(&mut self, field: &str, tropism: &Tropism) {
+        if tropism.dir.length_squared() == 0.0 {
+            self.push(
+                Severity::Warning,
+                &format!("{field}.dir"),
+                "zero vector has no direction, growth is not bent".to_string(),
+            );
+        }
+        let strengths = match tropism.strength {
+            TropismStrength::Value(value) => vec![value],
+            TropismStrength::IterationScale { start, end } => vec![start, end],
+        };
+        for strength in strengths {
+            if !(0.0..=1.0).contains(&strength) {
+                self.push(
+                    Severity::Warning,
+                    &format!("{field}.strength"),
+                    format!("{strength} is outside 0.0..=1.0 and will be clamped"),
+                );
+            }
+        }
+    }
+
+    fn assign_tags(&mut self, field: &str, tags: &[BranchTag]) {
+        for (i, tag) in tags.iter().enumerate() {
+            self.tag_resolved(&format!("{field}[{i}]"), tag);
+            self.assigned_tags.insert(tag.name.clone());
+        }
+    }
+
+    fn tag_resolved(&mut self, field: &str, tag: &BranchTag) {
+        if tag.mask == 0 {
+            self.push(
+                Severity::Error,
+                field,
+                format!(
+                    "tag \"{}\" is unresolved, call resolve_tags or use at most {MAX_BRANCH_TAGS} names",
+                    tag.name
+                ),
+            );
+        }
+    }
+
+    fn filter(&mut self, field: &str, filter: &Filter) {
+        self.id_filter(&format!("{field}.id"), &filter.id);
+        self.filter_expr(&format!("{field}.expr"), &filter.expr);
+    }
+
+    fn id_filter(&mut self, field: &str, id_filter: &IdFilter) {
+        match id_filter {
+            IdFilter::Target(id) if !self.assigned_ids.contains(id) => self.push(
+                Severity::Error,
+                field,
+                format!("no earlier step assigns id {id}, the filter matches no branches"),
+            ),
+            IdFilter::Tag(tag) => {
+                self.tag_resolved(field, tag);
+                if !self.assigned_tags.contains(&tag.name) {
+                    self.push(
+                        Severity::Error,
+                        field,
+                        format!(
+                            "no earlier step assigns tag \"{}\", the filter matches no branches",
+                            tag.name
+                        ),
+                    );
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn filter_expr(&mut self, field: &str, expr: &FilterExpr) {
+        let empty_range = match expr {
+            FilterExpr::All(exprs) | FilterExpr::Any(exprs) => {
+                for (i, expr) in exprs.iter().enumerate() {
+                    self.filter_expr(&format!("{field}[{i}]"), expr);
+                }
+                None
+            }
+            FilterExpr::Not(expr) => {
+                self.filter_expr(field, expr);
+                None
+            }
+            FilterExpr::Chance(percent) => {
+                if !(0.0..=100.0).contains(percent) {
+                    self.push(
+                        Severity::Warning,
+                        field,
+                        format!("Chance({percent}) is outside 0.0..=100.0"),
+                    );
+                }
+                None
+            }
+            FilterExpr::Height(range)
+            | FilterExpr::Thickness(range)
+            | FilterExpr::RootDistance(range) => {
+                (range.min > range.max).then(|| format!("{} > {}", range.min, range.max))
+            }
+            FilterExpr::Depth(range) | FilterExpr::ChildCount(range) => {
+                (range.min > range.max).then(|| format!("{} > {}", range.min, range.max))
+            }
+            FilterExpr::Id(id_filter) => {
+                self.id_filter(field, id_filter);
+                None
+            }
+            FilterExpr::Iteration(_) | FilterExpr::DirectionCone { .. } => None,
+        };
+        if let Some(bounds) = empty_range {
+            self.push(
+                Severity::Warning,
+                field,
+                format!("range min > max ({bounds}), matches no branches"),
+            );
+        }
+    }
+
+    fn range_f32(&mut self, field: &str, value: &ValueOrRangeF32) {
+        if let ValueOrRangeF32::Range(min, max) = value
+            && min > max
+        {
+            self.push(
+                Severity::Warning,
+                field,
+                format!("range min ({min}) > max ({max}), always resolves to {max}"),
+            );
+        }
+    }
+
+    fn range_u32(&mut self, field: &str, value: &ValueOrRangeU32) {
+        if let ValueOrRangeU32::Range(min, max) = value
+            && min > max
+        {
+            self.push(
+                Severity::Warning,
+                field,
+                format!("range min ({min}) > max ({max}), always resolves to {max}"),
+            );
+        }
+    }
+
+    fn decoration_selector(&mut self, field: &str, selector: &DecorationSelector) {
+        match selector {
+            DecorationSelector::Value(decoration) => self.leaf_decoration(field, decoration),
+            DecorationSelector::Random(decorations) => {
+                if decorations.is_empty() {
+                    self.push(
+                        Severity::Error,
+                        field,
+                        "Random list is empty, nothing to pick from".to_string(),
+                    );
+                }
+                for (i, decoration) in decorations.iter().enumerate() {
+                    self.leaf_decoration(&format!("{field}[{i}]"), decoration);
+                }
+            }
+            DecorationSelector::RandomWeighted(entries) => {
+                if entries.iter().all(|entry| entry.weight == 0) {
+                    self.push(
+                        Severity::Error,
+                        field,
+                        "RandomWeighted has no entry with a positive weight".to_string(),
+                    );
+                }
+                for (i, entry) in entries.iter().enumerate() {
+                    let entry_field = format!("{field}[{i}]");
+                    if entry.weight == 0 && entries.len() > 1 {
+                        self.push(
+                            Severity::Warning,
+                            &format!("{entry_field}.weight"),
+                            "zero weight, this entry is never picked".to_string(),
+                        );
+                    }
+                    self.leaf_decoration(&format!("{entry_field}.voxel"), &entry.voxel);
+                }
+            }
+        }
+    }
+
+    fn leaf_decoration(&mut self, field: &str, decoration: &LeafDecoration) {
+        match decoration {
+            LeafDecoration::Solid(_) => (),
+            LeafDecoration::RandomSolid(entries) => {
+                if entries.iter().all(|entry| entry.weight <= 0) {
+                    self.push(
+                        Severity::Error,
+                        field,
+                        "RandomSolid has no entry with a positive weight".to_string(),
+                    );
+                }
+                for (i, entry) in entries.iter().enumerate() {
+                    if entry.weight <= 0 && entries.len() > 1 {
+                        self.push(
+                            Severity::Warning,
+                            &format!("{field}[{i}].weight"),
+                            format!("weight {} is never picked", entry.weight),
+                        );
+                    }
+                }
+            }
+            LeafDecoration::Gradient(gradient) => {
+                if gradient.steps.is_empty() {
+                    self.push(
+                        Severity::Error,
+                        &format!("{field}.steps"),
+                        "Gradient has no steps".to_string(),
+                    );
+                }
+                for (i, pair) in gradient.steps.windows(2).enumerate() {
+                    if pair[1].percent < pair[0].percent {
+                        self.push(
+                            Severity::Error,
+                            &format!("{field}.steps[{}].percent", i + 1),
+                            format!(
+                                "{} is lower than the previous step ({}), steps must be sorted ascending",
+                                pair[1].percent, pair[0].percent
+                            ),
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
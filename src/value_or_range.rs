@@ -19,6 +19,13 @@ impl Default for ValueOrRangeU32 {
 }
 
 impl ValueOrRangeU32 {
+    /// Largest value [`Self::get`] can return.
+    pub fn max(&self) -> u32 {
+        match self {
+            ValueOrRangeU32::Value(v) => *v,
+            ValueOrRangeU32::Range(_, m) => *m,
+        }
+    }
     pub fn get(&self, rng: &mut ChaCha8Rng) -> u32 {
         match self {
             ValueOrRangeU32::Value(v) => *v,
@@ -51,7 +58,19 @@ impl ValueOrRangeF32 {
     pub fn get(&self, rng: &mut ChaCha8Rng) -> f32 {
         match self {
             ValueOrRangeF32::Value(v) => *v,
-            ValueOrRangeF32::Range(min, max) => rng.random_range((*min).min(*max)..=*max),
+            ValueOrRangeF32::Range(min, max) => random_range_f32(rng, (*min).min(*max), *max),
         }
     }
 }
+
+/// `rng.random_range(min..=max)`, except ranges it would panic on (empty, NaN
+/// or infinite bounds) resolve to the first finite of `max`, `min` or `0.0`.
+pub(crate) fn random_range_f32(rng: &mut ChaCha8Rng, min: f32, max: f32) -> f32 {
+    if min <= max && (max - min).is_finite() {
+        return rng.random_range(min..=max);
+    }
+    [max, min, 0.0]
+        .into_iter()
+        .find(|v| v.is_finite())
+        .unwrap_or_default()
+}
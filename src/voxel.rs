@@ -10,7 +10,12 @@ use rand_chacha::ChaCha8Rng;
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
-use crate::{prelude::ShrubberyGenerator, value_or_range::ValueOrRangeF32};
+use crate::{
+    error::ShrubberyError,
+    prelude::ShrubberyGenerator,
+    shrubbery::BranchCurve,
+    value_or_range::{ValueOrRangeF32, random_range_f32},
+};
 const EPSILON: f32 = 0.0001;
 
 /// Logs through bevy when the `bevy` feature is on, otherwise to stderr —
@@ -254,7 +259,7 @@ impl LeafDecoration {
                 if let Some(modulation) = &gradient_settings.modulation {
                     percent += match modulation {
                         LeafGradientModulation::Random { percent_offset } => {
-                            rng.random_range(-*percent_offset..=*percent_offset)
+                            random_range_f32(rng, -*percent_offset, *percent_offset)
                         }
                         LeafGradientModulation::Wave {
                             frequency,
@@ -344,38 +349,42 @@ pub struct ConiferWhorlShape {
     pub whorl_spacing: f32,
 }
 
-/// Largest leaf radius across all leaf groups, for bounding-box padding.
-fn leaf_padding(generator: &ShrubberyGenerator) -> i32 {
-    generator
-        .leaf_groups
-        .iter()
-        .map(|(shape, _)| match shape {
-            Shape::Sphere { radius: r } => r.max().ceil() as i32,
-            Shape::ConiferWhorl(w) => w.max_branch_length.ceil() as i32,
-            Shape::StarLeaf(s) => s.arm_length.max().ceil() as i32,
-        })
-        .max()
-        .unwrap_or(0)
-}
-
 type BranchMap = ahash::HashMap<IVec3, (f32, VoxelId)>;
 type VoxelMap = ahash::HashMap<IVec3, VoxelId>;
 
 impl ShrubberyGenerator {
     /// Rasterize the generated branches and shapes into a flat set of voxels.
+    /// Branches and shapes that fail are logged and skipped, see [`Self::try_voxelize`].
     pub fn voxelize(&mut self) -> Vec<(IVec3, VoxelId)> {
-        let (mut min_bounds, mut max_bounds) = self.bounds();
-        let padding = leaf_padding(self);
-        min_bounds -= IVec3::splat(padding);
-        max_bounds += IVec3::splat(padding);
+        let mut errors = Vec::new();
+        let voxels = self.voxelize_collecting_errors(&mut errors);
+        for error in errors {
+            log_error!("{error}");
+        }
+        voxels
+    }
 
+    /// Like [`Self::voxelize`], but fails on the first branch or shape that can't be voxelized.
+    pub fn try_voxelize(&mut self) -> Result<Vec<(IVec3, VoxelId)>, ShrubberyError> {
+        let mut errors = Vec::new();
+        let voxels = self.voxelize_collecting_errors(&mut errors);
+        match errors.into_iter().next() {
+            Some(error) => Err(error),
+            None => Ok(voxels),
+        }
+    }
+
+    fn voxelize_collecting_errors(
+        &mut self,
+        errors: &mut Vec<ShrubberyError>,
+    ) -> Vec<(IVec3, VoxelId)> {
         let mut voxels = BranchMap::default();
-        process_branches(self, &mut voxels);
+        process_branches(self, &mut voxels, errors);
         let mut voxel_map: VoxelMap = voxels
             .into_iter()
             .map(|(pos, (_dist, voxel_id))| (pos, voxel_id))
             .collect();
-        process_shapes(self, &mut voxel_map);
+        process_shapes(self, &mut voxel_map, errors);
 
         voxel_map.into_iter().collect()
     }
@@ -396,7 +405,7 @@ fn process_sphere_leaves(
     {
         let mut rng = ChaCha8Rng::seed_from_u64(branch_index as u64 + generator.seed);
         let r = radius.get(&mut rng);
-        let ri = r.ceil() as i32 + 1;
+        let ri = (r.ceil() as i32).saturating_add(1);
         let iteration_percent = branch.iteration as f32 / branch.iteration_total as f32;
         let bounds_min = branch.pos - Vec3::splat(r);
         let bounds_max = branch.pos + Vec3::splat(r);
@@ -470,13 +479,23 @@ fn voxelize_star_leaves(
     }
 }
 
-fn process_shapes(shrubbery: &mut ShrubberyGenerator, voxels: &mut VoxelMap) {
+fn process_shapes(
+    shrubbery: &mut ShrubberyGenerator,
+    voxels: &mut VoxelMap,
+    errors: &mut Vec<ShrubberyError>,
+) {
     for (leaf_index, (leaf_shape, leaf_decoration_selector)) in
         shrubbery.leaf_groups.iter().enumerate()
     {
         let leaf_decoration = leaf_decoration_selector.get_leaf_decoration(&mut shrubbery.rng);
         let Some(leaf_decoration) = leaf_decoration else {
-            log_error!("leaf decoration is None");
+            errors.push(ShrubberyError::EmptyShapeDecoration {
+                step_index: shrubbery
+                    .leaf_group_steps
+                    .get(leaf_index)
+                    .copied()
+                    .unwrap_or_default(),
+            });
             continue;
         };
 
@@ -506,69 +525,171 @@ fn process_shapes(shrubbery: &mut ShrubberyGenerator, voxels: &mut VoxelMap) {
     }
 }
 
-fn process_branches(shrubbery: &mut ShrubberyGenerator, voxels: &mut BranchMap) {
+fn process_branches(
+    shrubbery: &mut ShrubberyGenerator,
+    voxels: &mut BranchMap,
+    errors: &mut Vec<ShrubberyError>,
+) {
+    // the segment continuing each curved branch's chain, the first child of its
+    // group starting at the branch's end
+    let mut continuations: Vec<Option<usize>> = vec![None; shrubbery.branches.len()];
+    for (branch_index, branch) in shrubbery.branches.iter().enumerate().rev() {
+        if let Some(parent_index) = branch.parent_index
+            && let Some(parent) = shrubbery.branches.get(parent_index)
+            && parent.decoration_group == branch.decoration_group
+            && branch.start_pos == parent.pos
+        {
+            continuations[parent_index] = Some(branch_index);
+        }
+    }
+
     for (branch_index, branch) in shrubbery.branches.iter().enumerate() {
         let Some(parent_index) = branch.parent_index else {
             continue;
         };
-        let start_pos = shrubbery.branches[parent_index].pos;
-        let end_pos = branch.pos;
+        let Some(parent) = shrubbery.branches.get(parent_index) else {
+            errors.push(ShrubberyError::UnknownParent {
+                branch_index,
+                parent_index,
+            });
+            continue;
+        };
 
-        let min = start_pos.min(end_pos) - Vec3::splat(branch.thickness + 1.0);
-        let max = start_pos.max(end_pos) + Vec3::splat(branch.thickness + 1.0);
+        let curve = branch
+            .decoration_group
+            .and_then(|group| shrubbery.branch_curves.get(group))
+            .cloned()
+            .unwrap_or_default();
+        let pieces = match curve {
+            BranchCurve::Straight => {
+                vec![(
+                    branch.start_pos,
+                    branch.pos,
+                    branch.start_thickness,
+                    branch.thickness,
+                )]
+            }
+            BranchCurve::CatmullRom { subdivisions } => {
+                let start = branch.start_pos;
+                // the previous chain point is the start of the parent's segment
+                let before = match parent.parent_index {
+                    Some(_) if start == parent.pos => parent.start_pos,
+                    _ => start * 2.0 - branch.pos,
+                };
+                let after = continuations[branch_index].map_or(branch.pos * 2.0 - start, |child| {
+                    shrubbery.branches[child].pos
+                });
+                let start_thickness = branch.start_thickness;
+                let subdivisions = subdivisions.max(1);
+                let point = |i: u32| {
+                    let t = i as f32 / subdivisions as f32;
+                    let pos = catmull_rom(before, start, branch.pos, after, t);
+                    (
+                        pos,
+                        start_thickness + (branch.thickness - start_thickness) * t,
+                    )
+                };
+                (0..subdivisions)
+                    .map(|i| {
+                        let ((start, start_radius), (end, end_radius)) = (point(i), point(i + 1));
+                        (start, end, start_radius, end_radius)
+                    })
+                    .collect()
+            }
+        };
+        let max_radius = branch.thickness.max(branch.start_thickness);
+        let (min, max) = pieces.iter().fold(
+            (Vec3::splat(f32::MAX), Vec3::splat(f32::MIN)),
+            |(min, max), (start, end, _, _)| (min.min(start.min(*end)), max.max(start.max(*end))),
+        );
+        let min = min - Vec3::splat(max_radius + 1.0);
+        let max = max + Vec3::splat(max_radius + 1.0);
         let (min, max) = (min.floor().as_ivec3(), max.ceil().as_ivec3());
 
-        let Some(group_id) = branch.decoration_group else {
-            panic!("branch has no decoration group");
+        let Some(group) = branch.decoration_group else {
+            errors.push(ShrubberyError::MissingDecorationGroup { branch_index });
+            continue;
+        };
+        let Some(decoration_selector) = shrubbery.branch_decorations.get(group) else {
+            errors.push(ShrubberyError::UnknownDecorationGroup {
+                branch_index,
+                group,
+            });
+            continue;
         };
-        let decoration_selector = shrubbery
-            .branch_decorations
-            .get(group_id)
-            .expect("decoration group exists");
 
         let Some(decoration) = decoration_selector.get_leaf_decoration(&mut shrubbery.rng) else {
-            panic!("decoration selector resolved to None");
+            errors.push(ShrubberyError::EmptyBranchDecoration {
+                step_index: shrubbery
+                    .branch_decoration_steps
+                    .get(group)
+                    .copied()
+                    .unwrap_or_default(),
+                branch_index,
+            });
+            continue;
         };
 
-        for x in min.x..=max.x {
-            for y in min.y..=max.y {
-                for z in min.z..=max.z {
-                    let pos = ivec3(x, y, z);
-                    let sample = pos.as_vec3();
-                    let dist = point_segment_distance_squared(sample, start_pos, end_pos);
-                    if dist >= (branch.thickness + EPSILON).powi(2) {
-                        continue;
+        for (start_pos, end_pos, start_radius, end_radius) in pieces {
+            let piece_radius = start_radius.max(end_radius);
+            let piece_min = (start_pos.min(end_pos) - Vec3::splat(piece_radius + 1.0))
+                .floor()
+                .as_ivec3();
+            let piece_max = (start_pos.max(end_pos) + Vec3::splat(piece_radius + 1.0))
+                .ceil()
+                .as_ivec3();
+            for x in piece_min.x..=piece_max.x {
+                for y in piece_min.y..=piece_max.y {
+                    for z in piece_min.z..=piece_max.z {
+                        let pos = ivec3(x, y, z);
+                        let sample = pos.as_vec3();
+                        let dist = point_segment_distance_squared(sample, start_pos, end_pos);
+                        let inside = if start_radius == end_radius {
+                            dist < (end_radius + EPSILON).powi(2)
+                        } else {
+                            round_cone_distance(
+                                sample,
+                                start_pos,
+                                end_pos,
+                                start_radius,
+                                end_radius,
+                            ) < EPSILON
+                        };
+                        if !inside {
+                            continue;
+                        }
+                        let mut branch_rng = rand_chacha::ChaCha8Rng::seed_from_u64(
+                            branch_index as u64 + shrubbery.seed,
+                        );
+
+                        let iteration_percent = (branch.iteration as f32
+                            / branch.iteration_total as f32)
+                            .clamp(0.0, 1.0);
+                        let voxel_id = decoration.get_voxel_id(
+                            &mut branch_rng,
+                            sample,
+                            min.as_vec3(),
+                            max.as_vec3(),
+                            iteration_percent,
+                        );
+
+                        voxels
+                            .entry(pos)
+                            .and_modify(|(best, id)| {
+                                if dist < *best {
+                                    *best = dist;
+                                    *id = voxel_id;
+                                }
+                            })
+                            .or_insert((dist, voxel_id));
                     }
-                    let mut branch_rng = rand_chacha::ChaCha8Rng::seed_from_u64(
-                        branch_index as u64 + shrubbery.seed,
-                    );
-
-                    let iteration_percent =
-                        (branch.iteration as f32 / branch.iteration_total as f32).clamp(0.0, 1.0);
-                    let voxel_id = decoration.get_voxel_id(
-                        &mut branch_rng,
-                        sample,
-                        min.as_vec3(),
-                        max.as_vec3(),
-                        iteration_percent,
-                    );
-
-                    voxels
-                        .entry(pos)
-                        .and_modify(|(best, id)| {
-                            if dist < *best {
-                                *best = dist;
-                                *id = voxel_id;
-                            }
-                        })
-                        .or_insert((dist, voxel_id));
                 }
             }
         }
     }
 }
 
-fn point_segment_distance_squared(point: Vec3, start: Vec3, end: Vec3) -> f32 {
+pub(crate) fn point_segment_distance_squared(point: Vec3, start: Vec3, end: Vec3) -> f32 {
     let seg = end - start;
     let seg_len_sq = seg.length_squared();
 
@@ -582,6 +703,49 @@ fn point_segment_distance_squared(point: Vec3, start: Vec3, end: Vec3) -> f32 {
     point.distance_squared(closest)
 }
 
+/// Signed distance from `point` to a tapered capsule: spheres of `start_radius`
+/// and `end_radius` joined by their tangent cone.
+pub(crate) fn round_cone_distance(
+    point: Vec3,
+    start: Vec3,
+    end: Vec3,
+    start_radius: f32,
+    end_radius: f32,
+) -> f32 {
+    let seg = end - start;
+    let len_sq = seg.length_squared();
+    let radius_diff = start_radius - end_radius;
+    let cone_sq = len_sq - radius_diff * radius_diff;
+    // one sphere swallows the other, no cone joins them
+    if cone_sq <= EPSILON {
+        return (point.distance(start) - start_radius).min(point.distance(end) - end_radius);
+    }
+
+    let to_point = point - start;
+    let y = to_point.dot(seg);
+    let z = y - len_sq;
+    let x_sq = (to_point * len_sq - seg * y).length_squared();
+    let y_sq = y * y * len_sq;
+    let z_sq = z * z * len_sq;
+    let k = radius_diff.signum() * radius_diff * radius_diff * x_sq;
+    if z.signum() * cone_sq * z_sq > k {
+        return (x_sq + z_sq).sqrt() / len_sq - end_radius;
+    }
+    if y.signum() * cone_sq * y_sq < k {
+        return (x_sq + y_sq).sqrt() / len_sq - start_radius;
+    }
+    ((x_sq * cone_sq / len_sq).sqrt() + y * radius_diff) / len_sq - start_radius
+}
+
+/// Uniform Catmull-Rom spline between `p1` (t = 0) and `p2` (t = 1).
+fn catmull_rom(p0: Vec3, p1: Vec3, p2: Vec3, p3: Vec3, t: f32) -> Vec3 {
+    let (t2, t3) = (t * t, t * t * t);
+    0.5 * (p1 * 2.0
+        + (p2 - p0) * t
+        + (p0 * 2.0 - p1 * 5.0 + p2 * 4.0 - p3) * t2
+        + (p1 * 3.0 - p0 - p2 * 3.0 + p3) * t3)
+}
+
 fn conifer_taper_t(taper: &ConiferTaper, pos_y: f32, iteration: u32) -> f32 {
     match taper {
         ConiferTaper::Height { min_y, max_y } => {
@@ -620,7 +784,10 @@ fn voxelize_conifer_whorls(
         .iter()
         .filter(|b| b.leaf_group == Some(group_idx))
     {
-        let Some(parent_index) = branch.parent_index else {
+        let Some(parent) = branch
+            .parent_index
+            .and_then(|parent_index| generator.branches.get(parent_index))
+        else {
             // No parent segment to interpolate — fall back to one whorl at
             // the branch endpoint (e.g. the root branch).
             let taper_t = conifer_taper_t(&whorl.taper, branch.pos.y, branch.iteration);
@@ -637,12 +804,11 @@ fn voxelize_conifer_whorls(
             continue;
         };
 
-        let parent = &generator.branches[parent_index];
-        let seg = branch.pos - parent.pos; // segment vector — carries any lean/angle
+        let seg = branch.pos - branch.start_pos; // segment vector — carries any lean/angle
         let seg_len = seg.length();
         let steps = (seg_len / spacing).ceil().max(1.0) as u32;
 
-        let taper_t_start = conifer_taper_t(&whorl.taper, parent.pos.y, parent.iteration);
+        let taper_t_start = conifer_taper_t(&whorl.taper, branch.start_pos.y, parent.iteration);
         let taper_t_end = conifer_taper_t(&whorl.taper, branch.pos.y, branch.iteration);
 
         // t in (0,1], so each sub-layer's root center walks up the leaning
@@ -651,7 +817,7 @@ fn voxelize_conifer_whorls(
         let iteration_percent = branch.iteration as f32 / branch.iteration_total as f32;
         for s in 0..steps {
             let t = (s + 1) as f32 / steps as f32;
-            let pos = parent.pos + seg * t; // interpolated root center, tracks lean
+            let pos = branch.start_pos + seg * t; // interpolated root center, tracks lean
             let taper_t = taper_t_start + (taper_t_end - taper_t_start) * t;
             whorl_infos.push(WhorlInfo {
                 layer_index: layer_counter,
@@ -668,7 +834,7 @@ fn voxelize_conifer_whorls(
         let mut seed_rng = ChaCha8Rng::seed_from_u64(info.layer_index as u64 + generator.seed);
         let length_jitter = if whorl.length_jitter_ratio > 0.0 {
             let max_j = whorl.max_branch_length * whorl.length_jitter_ratio;
-            seed_rng.random_range(-max_j..=max_j)
+            random_range_f32(&mut seed_rng, -max_j, max_j)
         } else {
             0.0
         };
@@ -0,0 +1,284 @@
+//! dense voxel storage with a deterministic layout
+//! an alternative to the unordered `Vec<(IVec3, VoxelId)>` from [`ShrubberyGenerator::voxelize`]
+use glam::{IVec3, UVec3};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{error::VoxelGridError, shrubbery::ShrubberyGenerator, voxel::VoxelId};
+
+/// Palette compressed, dense box of voxels.
+///
+/// Cells are stored flat with x varying fastest, then y, then z. Each cell holds
+/// `0` for empty or `palette index + 1`, see [`VoxelGrid::cells`].
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(try_from = "VoxelGridData")
+)]
+pub struct VoxelGrid {
+    origin: IVec3,
+    size: UVec3,
+    palette: Vec<VoxelId>,
+    cells: Vec<u32>,
+}
+
+/// Unchecked serialized form of [`VoxelGrid`], validated by `TryFrom`.
+#[cfg(feature = "serde")]
+#[derive(Deserialize)]
+struct VoxelGridData {
+    origin: IVec3,
+    size: UVec3,
+    palette: Vec<VoxelId>,
+    cells: Vec<u32>,
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<VoxelGridData> for VoxelGrid {
+    type Error = VoxelGridError;
+
+    fn try_from(data: VoxelGridData) -> Result<Self, Self::Error> {
+        let expected = cell_count(data.size)?;
+        if data.cells.len() != expected {
+            return Err(VoxelGridError::CellCountMismatch {
+                expected,
+                found: data.cells.len(),
+            });
+        }
+        if let Some(&cell) = data
+            .cells
+            .iter()
+            .find(|cell| **cell as usize > data.palette.len())
+        {
+            return Err(VoxelGridError::PaletteIndexOutOfRange {
+                cell,
+                palette_len: data.palette.len(),
+            });
+        }
+        Ok(Self {
+            origin: data.origin,
+            size: data.size,
+            palette: data.palette,
+            cells: data.cells,
+        })
+    }
+}
+
+/// Most cells a [`VoxelGrid`] may hold, 256 MiB of cells.
+/// Far beyond any generated tree, but small enough to allocate.
+pub const MAX_CELLS: usize = 1 << 26;
+
+/// Cells in a grid of `size`, an error when the count exceeds [`MAX_CELLS`].
+fn cell_count(size: UVec3) -> Result<usize, VoxelGridError> {
+    (size.x as usize)
+        .checked_mul(size.y as usize)
+        .and_then(|xy| xy.checked_mul(size.z as usize))
+        .filter(|count| *count <= MAX_CELLS)
+        .ok_or(VoxelGridError::TooLarge { size })
+}
+
+impl VoxelGrid {
+    /// An empty grid covering `origin..origin + size`, at most [`MAX_CELLS`] cells.
+    pub fn new(origin: IVec3, size: UVec3) -> Result<Self, VoxelGridError> {
+        Ok(Self {
+            origin,
+            size,
+            palette: Vec::new(),
+            cells: vec![0; cell_count(size)?],
+        })
+    }
+
+    /// Smallest grid containing every voxel. Later duplicates overwrite earlier ones.
+    pub fn from_voxels(voxels: &[(IVec3, VoxelId)]) -> Result<Self, VoxelGridError> {
+        let Some((first, _)) = voxels.first() else {
+            return Ok(Self::default());
+        };
+        let (min, max) = voxels
+            .iter()
+            .fold((*first, *first), |(min, max), (pos, _)| {
+                (min.min(*pos), max.max(*pos))
+            });
+        // the span of two i32 can exceed i32, measure it in i64
+        let span = max.as_i64vec3() - min.as_i64vec3() + 1;
+        let size = UVec3::new(
+            u32::try_from(span.x).unwrap_or(u32::MAX),
+            u32::try_from(span.y).unwrap_or(u32::MAX),
+            u32::try_from(span.z).unwrap_or(u32::MAX),
+        );
+        if span.max_element() > u32::MAX as i64 {
+            return Err(VoxelGridError::TooLarge { size });
+        }
+        let mut grid = Self::new(min, size)?;
+        for (pos, voxel_id) in voxels {
+            grid.set(*pos, Some(*voxel_id));
+        }
+        Ok(grid)
+    }
+
+    /// World position of the minimum corner.
+    pub fn origin(&self) -> IVec3 {
+        self.origin
+    }
+
+    /// Cell count along each axis.
+    pub fn size(&self) -> UVec3 {
+        self.size
+    }
+
+    /// Distinct voxel ids referenced by [`Self::cells`].
+    pub fn palette(&self) -> &[VoxelId] {
+        &self.palette
+    }
+
+    /// Raw cells in layout order, `0` is empty, otherwise `palette[cell - 1]`.
+    pub fn cells(&self) -> &[u32] {
+        &self.cells
+    }
+
+    pub fn contains(&self, pos: IVec3) -> bool {
+        self.index(pos).is_some()
+    }
+
+    /// Flat index of `pos` into [`Self::cells`], `None` when outside the grid.
+    pub fn index(&self, pos: IVec3) -> Option<usize> {
+        let local = pos.as_i64vec3() - self.origin.as_i64vec3();
+        if local.min_element() < 0 || local.cmpge(self.size.as_i64vec3()).any() {
+            return None;
+        }
+        // in bounds, so this stays below the validated cell count
+        let (x, y, z) = (local.x as usize, local.y as usize, local.z as usize);
+        let (size_x, size_y) = (self.size.x as usize, self.size.y as usize);
+        Some(x + size_x * (y + size_y * z))
+    }
+
+    fn pos_from_index(&self, index: usize) -> IVec3 {
+        let (size_x, size_y) = (self.size.x as usize, self.size.y as usize);
+        let x = index % size_x;
+        let y = (index / size_x) % size_y;
+        let z = index / (size_x * size_y);
+        (self.origin.as_i64vec3() + glam::I64Vec3::new(x as i64, y as i64, z as i64)).as_ivec3()
+    }
+
+    pub fn get(&self, pos: IVec3) -> Option<VoxelId> {
+        let cell = self.cells[self.index(pos)?];
+        cell.checked_sub(1).map(|i| self.palette[i as usize])
+    }
+
+    /// Writes or clears (`None`) a voxel. Returns false if `pos` is outside the grid.
+    pub fn set(&mut self, pos: IVec3, voxel_id: Option<VoxelId>) -> bool {
+        let Some(index) = self.index(pos) else {
+            return false;
+        };
+        self.cells[index] = match voxel_id {
+            Some(voxel_id) => self.palette_cell(voxel_id),
+            None => 0,
+        };
+        true
+    }
+
+    fn palette_cell(&mut self, voxel_id: VoxelId) -> u32 {
+        let palette_index = match self.palette.iter().position(|id| *id == voxel_id) {
+            Some(i) => i,
+            None => {
+                self.palette.push(voxel_id);
+                self.palette.len() - 1
+            }
+        };
+        palette_index as u32 + 1
+    }
+
+    /// Number of filled cells.
+    pub fn len(&self) -> usize {
+        self.cells.iter().filter(|cell| **cell != 0).count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cells.iter().all(|cell| *cell == 0)
+    }
+
+    /// Filled voxels in layout order.
+    pub fn iter(&self) -> impl Iterator<Item = (IVec3, VoxelId)> + '_ {
+        self.cells.iter().enumerate().filter_map(|(index, cell)| {
+            let palette_index = cell.checked_sub(1)?;
+            Some((
+                self.pos_from_index(index),
+                self.palette[palette_index as usize],
+            ))
+        })
+    }
+
+    /// Filled voxels as the flat form returned by [`ShrubberyGenerator::voxelize`].
+    pub fn to_vec(&self) -> Vec<(IVec3, VoxelId)> {
+        self.iter().collect()
+    }
+
+    /// Splits into world aligned cubes of `chunk_size` (clamped to `1..=256`),
+    /// skipping empty chunks.
+    /// Every chunk shares this grid's palette, so raw cells stay comparable.
+    ///
+    /// Yields `(chunk coordinate, chunk)` where the chunk covers
+    /// `coordinate * chunk_size..(coordinate + 1) * chunk_size`, in z, y, x order.
+    pub fn chunks(&self, chunk_size: u32) -> impl Iterator<Item = (IVec3, VoxelGrid)> + '_ {
+        let chunk_size = chunk_size.clamp(1, 256) as i32;
+        let (min, max) = if self.cells.is_empty() {
+            (IVec3::ZERO, IVec3::NEG_ONE)
+        } else {
+            (
+                self.origin.div_euclid(IVec3::splat(chunk_size)),
+                (self.origin + self.size.as_ivec3() - IVec3::ONE)
+                    .div_euclid(IVec3::splat(chunk_size)),
+            )
+        };
+        (min.z..=max.z)
+            .flat_map(move |z| {
+                (min.y..=max.y).flat_map(move |y| (min.x..=max.x).map(move |x| IVec3::new(x, y, z)))
+            })
+            .filter_map(move |coord| {
+                let mut chunk = VoxelGrid {
+                    origin: coord * chunk_size,
+                    size: UVec3::splat(chunk_size as u32),
+                    palette: self.palette.clone(),
+                    cells: vec![0; (chunk_size * chunk_size * chunk_size) as usize],
+                };
+                let mut filled = false;
+                for z in 0..chunk_size {
+                    for y in 0..chunk_size {
+                        for x in 0..chunk_size {
+                            let pos = chunk.origin + IVec3::new(x, y, z);
+                            let Some(index) = self.index(pos) else {
+                                continue;
+                            };
+                            let cell = self.cells[index];
+                            if cell != 0 {
+                                let chunk_index = x + chunk_size * (y + chunk_size * z);
+                                chunk.cells[chunk_index as usize] = cell;
+                                filled = true;
+                            }
+                        }
+                    }
+                }
+                filled.then_some((coord, chunk))
+            })
+    }
+}
+
+impl TryFrom<&[(IVec3, VoxelId)]> for VoxelGrid {
+    type Error = VoxelGridError;
+
+    fn try_from(voxels: &[(IVec3, VoxelId)]) -> Result<Self, Self::Error> {
+        Self::from_voxels(voxels)
+    }
+}
+
+impl From<&VoxelGrid> for Vec<(IVec3, VoxelId)> {
+    fn from(grid: &VoxelGrid) -> Self {
+        grid.to_vec()
+    }
+}
+
+impl ShrubberyGenerator {
+    /// [`Self::voxelize`] into a dense [`VoxelGrid`].
+    pub fn voxelize_grid(&mut self) -> Result<VoxelGrid, VoxelGridError> {
+        VoxelGrid::from_voxels(&self.voxelize())
+    }
+}
@@ -0,0 +1,235 @@
+//! Every [`AttractorShape`] fills its volume, thinned by an optional [`AttractorDensity`]
+//! and, on request, by the volume of the branches grown so far.
+
+use glam::{Vec3, vec3};
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use shrubbery_voxel::{
+    attractor::Attractor,
+    prelude::*,
+    shape::{AttractorDensity, AttractorShape, CubeShape},
+    shrubbery::{
+        AttractorDistribution, AttractorSpacing, BranchThickness, GrowStep, InitialDir,
+        SpawnAttractorLocation, SpawnAttractors, SpawnRootStep, StepChance,
+    },
+};
+
+const SPACING: AttractorSpacing = AttractorSpacing {
+    attractor_spacing: 1.0,
+    jitter_ratio: 1.0,
+    distribution: AttractorDistribution::JitteredGrid,
+};
+
+fn generate(shape: &AttractorShape, pos: Vec3) -> Vec<Attractor> {
+    let mut attractors = Vec::new();
+    shape.generate(
+        pos,
+        &mut attractors,
+        &SPACING,
+        &mut ChaCha8Rng::seed_from_u64(9),
+    );
+    attractors
+}
+
+#[test]
+fn attractors_stay_inside_their_shape() {
+    let pos = vec3(3.0, 10.0, -2.0);
+    let shapes = [
+        AttractorShape::Sphere { radius: 4.0 },
+        AttractorShape::Ellipsoid {
+            radius_x: 6.0,
+            radius_y: 2.0,
+            radius_z: 3.0,
+        },
+        AttractorShape::Hemisphere { radius: 4.0 },
+        AttractorShape::Cylinder {
+            radius: 3.0,
+            height: 6.0,
+        },
+        AttractorShape::Cone {
+            radius: 4.0,
+            height: 8.0,
+        },
+        AttractorShape::Torus {
+            major_radius: 5.0,
+            minor_radius: 1.5,
+        },
+        AttractorShape::Capsule {
+            start: Vec3::ZERO,
+            end: vec3(0.0, 8.0, 4.0),
+            radius: 2.0,
+        },
+    ];
+    for shape in shapes {
+        let attractors = generate(&shape, pos);
+        let (min, max) = shape.bounds();
+        let cells = ((max - min) / SPACING.attractor_spacing)
+            .ceil()
+            .element_product();
+        assert!(!attractors.is_empty(), "{shape:?} spawned nothing");
+        assert!(
+            (attractors.len() as f32) < cells,
+            "{shape:?} rejected no cells"
+        );
+        for attractor in &attractors {
+            assert!(shape.contains(attractor.pos - pos), "{shape:?}");
+        }
+    }
+}
+
+#[test]
+fn cube_keeps_every_cell() {
+    let cube = AttractorShape::Cube(CubeShape {
+        size_x: 4.0,
+        size_y: 2.5,
+        size_z: 3.0,
+    });
+    assert_eq!(generate(&cube, Vec3::ZERO).len(), 4 * 3 * 3);
+}
+
+#[test]
+fn poisson_disk_keeps_min_distance() {
+    let spacing = AttractorSpacing {
+        attractor_spacing: 1.5,
+        distribution: AttractorDistribution::PoissonDisk { attempts: 30 },
+        ..SPACING
+    };
+    let shapes = [
+        AttractorShape::Sphere { radius: 6.0 },
+        AttractorShape::Cube(CubeShape {
+            size_x: 8.0,
+            size_y: 3.0,
+            size_z: 8.0,
+        }),
+        AttractorShape::Torus {
+            major_radius: 5.0,
+            minor_radius: 1.5,
+        },
+    ];
+    for shape in shapes {
+        let mut attractors = Vec::new();
+        shape.generate(
+            Vec3::ZERO,
+            &mut attractors,
+            &spacing,
+            &mut ChaCha8Rng::seed_from_u64(4),
+        );
+        assert!(attractors.len() > 10, "{shape:?} barely filled");
+        for (i, a) in attractors.iter().enumerate() {
+            assert!(shape.contains(a.pos), "{shape:?}");
+            for b in &attractors[i + 1..] {
+                assert!(a.pos.distance(b.pos) >= 1.5, "{shape:?}");
+            }
+        }
+
+        let mut again = Vec::new();
+        shape.generate(
+            Vec3::ZERO,
+            &mut again,
+            &spacing,
+            &mut ChaCha8Rng::seed_from_u64(4),
+        );
+        let positions = |v: &[Attractor]| v.iter().map(|a| a.pos).collect::<Vec<_>>();
+        assert_eq!(positions(&attractors), positions(&again));
+    }
+}
+
+#[test]
+fn density_thins_and_weight_is_assigned() {
+    let spawn = |density: AttractorDensity| SpawnAttractors {
+        chance: StepChance::Always,
+        location: SpawnAttractorLocation::Pos(Vec3::ZERO),
+        shape: AttractorShape::Sphere { radius: 8.0 },
+        attractor_spacing: SPACING,
+        density,
+        weight: 2.5,
+        set: String::new(),
+        reject_inside_branches: false,
+    };
+    let generate = |spawn: &SpawnAttractors| {
+        let mut attractors = Vec::new();
+        spawn.generate(
+            vec3(0.0, 5.0, 0.0),
+            &mut attractors,
+            &mut ChaCha8Rng::seed_from_u64(2),
+        );
+        attractors
+    };
+
+    let uniform = generate(&spawn(AttractorDensity::Uniform));
+    assert!(uniform.iter().all(|a| a.weight == 2.5));
+    // uniform spawns exactly what the shape spawns
+    let mut unthinned = Vec::new();
+    AttractorShape::Sphere { radius: 8.0 }.generate(
+        vec3(0.0, 5.0, 0.0),
+        &mut unthinned,
+        &SPACING,
+        &mut ChaCha8Rng::seed_from_u64(2),
+    );
+    let positions = |v: &[Attractor]| v.iter().map(|a| a.pos).collect::<Vec<_>>();
+    assert_eq!(positions(&uniform), positions(&unthinned));
+
+    // thinning only drops attractors of the same shape, and weights the survivors
+    let thinned = generate(&spawn(AttractorDensity::Shell { power: 1.0 }));
+    assert!(!thinned.is_empty() && thinned.len() < uniform.len());
+    assert!(thinned.iter().all(|a| a.weight == 2.5));
+    let mut remaining = positions(&uniform).into_iter();
+    assert!(
+        thinned.iter().all(|a| remaining.any(|pos| pos == a.pos)),
+        "thinned attractors are an ordered subset of the uniform ones"
+    );
+
+    let mean_height = |attractors: &[Attractor]| {
+        attractors.iter().map(|a| a.pos.y).sum::<f32>() / attractors.len() as f32
+    };
+    let top = generate(&spawn(AttractorDensity::Top { power: 2.0 }));
+    assert!(top.len() < uniform.len());
+    assert!(mean_height(&top) > mean_height(&uniform) + 1.0);
+
+    let mean_distance = |attractors: &[Attractor]| {
+        attractors
+            .iter()
+            .map(|a| a.pos.distance(vec3(0.0, 5.0, 0.0)))
+            .sum::<f32>()
+            / attractors.len() as f32
+    };
+    let shell = generate(&spawn(AttractorDensity::Shell { power: 2.0 }));
+    let falloff = generate(&spawn(AttractorDensity::RadialFalloff { power: 2.0 }));
+    assert!(mean_distance(&shell) > mean_distance(&uniform));
+    assert!(mean_distance(&falloff) < mean_distance(&uniform));
+}
+
+#[test]
+fn branch_volumes_follow_the_taper() {
+    // the second segment widens from 0.5 at y = 6 to 4.0 at y = 12
+    let settings = ShrubberySettings {
+        build_steps: vec![
+            ShrubberyStep::SpawnRoot(SpawnRootStep {
+                initial_dir: InitialDir::Value(Vec3::Y),
+                ..Default::default()
+            }),
+            ShrubberyStep::Grow(GrowStep {
+                times: ValueOrRangeU32::Value(2),
+                length: ValueOrRangeF32::Value(6.0),
+                thickness: BranchThickness::IterationScale {
+                    min: ValueOrRangeF32::Value(0.5),
+                    max: ValueOrRangeF32::Value(4.0),
+                },
+                filter: Filter {
+                    ignore_root: false,
+                    ..Default::default()
+                },
+                ..Default::default()
+            }),
+        ],
+    };
+    let generator = ShrubberyGenerator::generate(11, &settings);
+    let segment = &generator.branches[2];
+    assert_eq!((segment.start_thickness, segment.thickness), (0.5, 4.0));
+
+    let volumes = generator.branch_volumes();
+    assert!(volumes.contains(vec3(2.5, 11.5, 0.0)));
+    // within the end radius, but outside the thin start of the segment
+    assert!(!volumes.contains(vec3(2.5, 7.0, 0.0)));
+    assert!(!volumes.contains(vec3(0.0, 16.5, 0.0)));
+}
@@ -0,0 +1,52 @@
+//! Broken settings voxelize into a [`ShrubberyError`] instead of panicking.
+
+use shrubbery_voxel::{
+    prelude::*,
+    shrubbery::{BranchThickness, GrowStep, SpawnRootStep, StepChance},
+    voxel::DecorationSelector,
+};
+
+fn generate(build_steps: Vec<ShrubberyStep>) -> ShrubberyGenerator {
+    ShrubberyGenerator::generate(7, &ShrubberySettings { build_steps })
+}
+
+fn root_and_grow(grow: GrowStep) -> Vec<ShrubberyStep> {
+    vec![
+        ShrubberyStep::SpawnRoot(SpawnRootStep::default()),
+        ShrubberyStep::Grow(GrowStep {
+            filter: Filter {
+                ignore_root: false,
+                ..Default::default()
+            },
+            ..grow
+        }),
+    ]
+}
+
+#[test]
+fn empty_random_selector_reports_step_and_branch() {
+    let steps = root_and_grow(GrowStep {
+        voxel: DecorationSelector::Random(vec![]),
+        ..Default::default()
+    });
+    assert_eq!(
+        generate(steps.clone()).try_voxelize(),
+        Err(ShrubberyError::EmptyBranchDecoration {
+            step_index: 1,
+            branch_index: 1,
+        })
+    );
+    // the lenient path logs and skips the branch
+    assert!(generate(steps).voxelize().is_empty());
+}
+
+#[test]
+fn out_of_range_values_do_not_panic() {
+    let steps = root_and_grow(GrowStep {
+        chance: StepChance::Chance(250.0),
+        length: ValueOrRangeF32::Range(f32::NAN, f32::INFINITY),
+        thickness: BranchThickness::ValueOrRange(ValueOrRangeF32::Range(3.0, -2.0)),
+        ..Default::default()
+    });
+    assert!(generate(steps).try_voxelize().is_ok());
+}
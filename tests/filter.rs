@@ -0,0 +1,143 @@
+//! [`FilterExpr`] predicates.
+
+use shrubbery_voxel::{
+    filter::{FilterRangeF32, FilterRangeU32},
+    prelude::*,
+    shrubbery::{BranchSpawnMethod, GrowRadial, GrowStep, InitialDir, SpawnRootStep},
+};
+
+/// A root growing 4 segments straight up, then 6 radial branches of 3 segments from its tip.
+fn generator() -> ShrubberyGenerator {
+    let steps = vec![
+        ShrubberyStep::SpawnRoot(SpawnRootStep {
+            initial_dir: InitialDir::Value(glam::Vec3::Y),
+            ..Default::default()
+        }),
+        ShrubberyStep::Grow(GrowStep {
+            times: ValueOrRangeU32::Value(4),
+            length: ValueOrRangeF32::Value(2.0),
+            filter: Filter {
+                ignore_root: false,
+                ..Default::default()
+            },
+            ..Default::default()
+        }),
+        ShrubberyStep::Grow(GrowStep {
+            times: ValueOrRangeU32::Value(3),
+            length: ValueOrRangeF32::Value(3.0),
+            spawn_method: BranchSpawnMethod::GrowRadial(GrowRadial {
+                count: ValueOrRangeU32::Value(6),
+                ..Default::default()
+            }),
+            filter: Filter {
+                iteration: IterationFilter::Last,
+                ..Default::default()
+            },
+            ..Default::default()
+        }),
+    ];
+    ShrubberyGenerator::generate(3, &ShrubberySettings { build_steps: steps })
+}
+
+fn matching(generator: &ShrubberyGenerator, expr: FilterExpr) -> Vec<usize> {
+    let filter = Filter {
+        ignore_root: false,
+        id: IdFilter::All,
+        expr,
+        ..Default::default()
+    };
+    let context = generator.filter_context();
+    (0..generator.branches.len())
+        .filter(|&i| filter.should_include_branch(i, &context))
+        .collect()
+}
+
+fn depth(min: u32, max: u32) -> FilterExpr {
+    FilterExpr::Depth(FilterRangeU32 { min, max })
+}
+
+fn child_count(min: u32, max: u32) -> FilterExpr {
+    FilterExpr::ChildCount(FilterRangeU32 { min, max })
+}
+
+fn max_height(max: f32) -> FilterExpr {
+    FilterExpr::Height(FilterRangeF32 {
+        max,
+        ..Default::default()
+    })
+}
+
+#[test]
+fn predicates() {
+    let generator = generator();
+    // root + 4 trunk segments + 6 * 3 radial segments
+    assert_eq!(generator.branches.len(), 23);
+
+    assert_eq!(matching(&generator, FilterExpr::All(vec![])).len(), 23);
+    assert_eq!(
+        matching(&generator, FilterExpr::Any(vec![])),
+        Vec::<usize>::new()
+    );
+    assert_eq!(matching(&generator, depth(1, 2)), vec![1, 2]);
+    assert_eq!(matching(&generator, max_height(4.0)), vec![0, 1, 2]);
+    assert_eq!(
+        matching(
+            &generator,
+            FilterExpr::RootDistance(FilterRangeF32 { min: 7.9, max: 8.1 })
+        ),
+        vec![4]
+    );
+    // the tip of each radial arm
+    assert_eq!(matching(&generator, child_count(0, 0)).len(), 6);
+    assert_eq!(matching(&generator, child_count(6, u32::MAX)), vec![4]);
+    assert_eq!(
+        matching(
+            &generator,
+            FilterExpr::Not(Box::new(FilterExpr::DirectionCone {
+                dir: glam::Vec3::Y,
+                max_degrees: 10.0,
+            }))
+        )
+        .len(),
+        18
+    );
+    assert_eq!(
+        matching(
+            &generator,
+            FilterExpr::All(vec![
+                FilterExpr::Any(vec![
+                    FilterExpr::Id(IdFilter::Target(1)),
+                    FilterExpr::Id(IdFilter::Target(2)),
+                ]),
+                max_height(4.0),
+                FilterExpr::Not(Box::new(depth(0, 0))),
+            ])
+        ),
+        vec![1, 2]
+    );
+}
+
+/// `child_count` counts the segments grown directly from a branch, so chained
+/// `Grow` segments each carry one child instead of the chain start carrying all.
+#[test]
+fn child_count_matches_direct_children() {
+    let generator = generator();
+    for (index, branch) in generator.branches.iter().enumerate() {
+        let children = generator
+            .branches
+            .iter()
+            .filter(|other| other.parent_index == Some(index))
+            .count();
+        assert_eq!(branch.child_count as usize, children, "branch {index}");
+    }
+}
+
+#[test]
+fn chance_is_deterministic_per_step() {
+    let generator = generator();
+    let half = matching(&generator, FilterExpr::Chance(50.0));
+    assert_eq!(half, matching(&generator, FilterExpr::Chance(50.0)));
+    assert!(!half.is_empty() && half.len() < generator.branches.len());
+    assert!(matching(&generator, FilterExpr::Chance(0.0)).is_empty());
+    assert_eq!(matching(&generator, FilterExpr::Chance(100.0)).len(), 23);
+}
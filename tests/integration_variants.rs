@@ -6,7 +6,7 @@
 mod common;
 
 use common::{discover_assets, load_shrubbery_settings};
-use shrubbery_voxel::prelude::*;
+use shrubbery_voxel::{prelude::*, validation::Severity};
 
 /// Generating twice with the same seed must produce identical voxels.
 #[test]
@@ -34,6 +34,126 @@ fn different_seeds_different_voxels() {
     }
 }
 
+/// The dense grid must hold exactly the voxels of the flat output.
+#[test]
+fn voxel_grid_matches_voxelize() {
+    for asset_name in discover_assets() {
+        let settings = load_shrubbery_settings(&asset_name);
+        let grid = ShrubberyGenerator::generate(42, &settings)
+            .voxelize_grid()
+            .unwrap();
+        let mut from_grid = grid.to_vec();
+        from_grid.sort_by_key(|(pos, _)| (pos.x, pos.y, pos.z));
+        assert_eq!(
+            from_grid,
+            sorted_voxels(42, &settings),
+            "voxel grid diverged from voxelize for {asset_name}"
+        );
+    }
+}
+
+/// Shipped assets must not have validation errors.
+#[test]
+fn assets_validate_without_errors() {
+    for asset_name in discover_assets() {
+        let errors: Vec<String> = load_shrubbery_settings(&asset_name)
+            .validate()
+            .into_iter()
+            .filter(|d| d.severity == Severity::Error)
+            .map(|d| d.to_string())
+            .collect();
+        assert!(errors.is_empty(), "{asset_name}: {errors:#?}");
+    }
+}
+
+/// Every branch starts on its parent's segment, between the parent's start and end.
+#[test]
+fn branches_start_on_their_parent() {
+    for asset_name in discover_assets() {
+        let settings = load_shrubbery_settings(&asset_name);
+        let generator = ShrubberyGenerator::generate(42, &settings);
+        for (index, branch) in generator.branches.iter().enumerate() {
+            let Some(parent_index) = branch.parent_index else {
+                assert_eq!(branch.start_pos, branch.pos, "{asset_name}: root {index}");
+                continue;
+            };
+            let parent = &generator.branches[parent_index];
+            let segment = parent.pos - parent.start_pos;
+            let t = (branch.start_pos - parent.start_pos).dot(segment)
+                / segment.length_squared().max(f32::EPSILON);
+            let closest = parent.start_pos + segment * t.clamp(0.0, 1.0);
+            assert!(
+                branch.start_pos.distance(closest) < 1e-3,
+                "{asset_name}: branch {index} starts off its parent"
+            );
+        }
+    }
+}
+
+/// Side branches flare out from their host at most as much as their length allows.
+#[test]
+fn side_branches_taper_within_their_length() {
+    for asset_name in discover_assets() {
+        let settings = load_shrubbery_settings(&asset_name);
+        let generator = ShrubberyGenerator::generate(42, &settings);
+        for (index, branch) in generator.branches.iter().enumerate() {
+            let flare = branch.start_thickness - branch.thickness;
+            let length = branch.pos.distance(branch.start_pos);
+            assert!(
+                flare <= length * 0.5 + 1e-4,
+                "{asset_name}: branch {index} flares {flare} over {length}"
+            );
+        }
+    }
+}
+
+/// Traversals and stream orders must agree with the parent links they are built from.
+#[test]
+fn hierarchy_matches_parent_links() {
+    for asset_name in discover_assets() {
+        let settings = load_shrubbery_settings(&asset_name);
+        let generator = ShrubberyGenerator::generate(42, &settings);
+        let branches = &generator.branches;
+        let strahler = generator.strahler_orders();
+        let horton = generator.horton_orders();
+        for (index, branch) in branches.iter().enumerate() {
+            let children: Vec<usize> = generator.children(index).collect();
+            let linked: Vec<usize> = (0..branches.len())
+                .filter(|&i| branches[i].parent_index == Some(index))
+                .collect();
+            assert_eq!(children, linked, "{asset_name}: children of {index}");
+            assert_eq!(
+                generator.ancestors(index).next(),
+                branch.parent_index,
+                "{asset_name}: parent of {index}"
+            );
+
+            // tips are 1, otherwise the highest child order, plus one when shared
+            let highest = children.iter().map(|&c| strahler[c]).max().unwrap_or(0);
+            let shared = children.iter().filter(|&&c| strahler[c] == highest).count() > 1;
+            let expected = match highest {
+                0 => 1,
+                _ if shared => highest + 1,
+                _ => highest,
+            };
+            assert_eq!(
+                strahler[index], expected,
+                "{asset_name}: strahler of {index}"
+            );
+            assert!(
+                horton[index] >= strahler[index],
+                "{asset_name}: horton of {index}"
+            );
+        }
+        // every branch sits below exactly one root
+        let subtrees: usize = (0..branches.len())
+            .filter(|&i| branches[i].parent_index.is_none())
+            .map(|root| 1 + generator.descendants(root).count())
+            .sum();
+        assert_eq!(subtrees, branches.len(), "{asset_name}");
+    }
+}
+
 /// Voxelize with `seed`, sorted by position since iteration order isn't guaranteed.
 fn sorted_voxels(
     seed: u64,
@@ -0,0 +1,61 @@
+//! Greedy meshing of [`VoxelGrid`]s.
+
+use glam::{IVec3, Vec3, ivec3};
+use shrubbery_voxel::{prelude::*, voxel::VoxelId};
+
+fn mesh(voxels: &[(IVec3, VoxelId)]) -> VoxelMesh {
+    VoxelGrid::from_voxels(voxels).unwrap().greedy_mesh()
+}
+
+#[test]
+fn single_voxel_is_a_cube() {
+    let mesh = mesh(&[(ivec3(2, -1, 0), VoxelId(4))]);
+    assert_eq!(mesh.quad_count(), 6);
+    assert_eq!(mesh.positions.len(), 24);
+    assert_eq!(mesh.indices.len(), 36);
+    assert!(mesh.face_voxels.iter().all(|id| *id == VoxelId(4)));
+    for p in &mesh.positions {
+        let p = Vec3::from_array(*p);
+        assert!(p.cmpge(Vec3::new(2.0, -1.0, 0.0)).all());
+        assert!(p.cmple(Vec3::new(3.0, 0.0, 1.0)).all());
+    }
+}
+
+#[test]
+fn same_voxels_merge_into_one_quad_per_side() {
+    let voxels: Vec<_> = (0..4)
+        .flat_map(|x| (0..3).map(move |z| (ivec3(x, 0, z), VoxelId(1))))
+        .collect();
+    let mesh = mesh(&voxels);
+    assert_eq!(mesh.quad_count(), 6);
+    let top = mesh
+        .normals
+        .iter()
+        .position(|n| *n == [0.0, 1.0, 0.0])
+        .expect("top face");
+    let [w, h] = mesh.uvs[top + 2];
+    assert_eq!(w * h, 12.0, "uvs span the merged face in voxel units");
+}
+
+#[test]
+fn shared_faces_are_culled_and_ids_kept_apart() {
+    let mesh = mesh(&[(ivec3(0, 0, 0), VoxelId(1)), (ivec3(1, 0, 0), VoxelId(2))]);
+    assert_eq!(mesh.quad_count(), 10);
+    assert_eq!(
+        mesh.face_voxels
+            .iter()
+            .filter(|id| **id == VoxelId(1))
+            .count(),
+        5
+    );
+}
+
+#[test]
+fn triangles_face_their_normal() {
+    let mesh = mesh(&[(ivec3(0, 0, 0), VoxelId(1)), (ivec3(0, 1, 1), VoxelId(1))]);
+    for triangle in mesh.indices.chunks(3) {
+        let [a, b, c] = [0, 1, 2].map(|i| Vec3::from_array(mesh.positions[triangle[i] as usize]));
+        let normal = Vec3::from_array(mesh.normals[triangle[0] as usize]);
+        assert!((b - a).cross(c - a).dot(normal) > 0.0);
+    }
+}
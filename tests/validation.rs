@@ -0,0 +1,171 @@
+//! [`ShrubberySettings::validate`] diagnostics for hand-built settings.
+
+use glam::Vec3;
+use shrubbery_voxel::{
+    prelude::*,
+    shape::{AttractorDensity, AttractorShape},
+    shrubbery::{
+        AttractorSettings, AttractorSpacing, BranchGrowthDirection, BranchSpawnMethod, Fork,
+        GrowStep, SpawnAttractorLocation, SpawnAttractors, SpawnRootStep, StepChance,
+    },
+    validation::{Diagnostic, Severity},
+    voxel::{DecorationSelector, LeafDecoration, LeafGradientEntry, LeafGradientSettings},
+};
+
+fn validate(build_steps: Vec<ShrubberyStep>) -> Vec<Diagnostic> {
+    ShrubberySettings { build_steps }.validate()
+}
+
+fn has(diagnostics: &[Diagnostic], step_index: usize, field: &str, severity: Severity) -> bool {
+    diagnostics
+        .iter()
+        .any(|d| d.step_index == step_index && d.field == field && d.severity == severity)
+}
+
+#[test]
+fn default_steps_are_valid() {
+    let diagnostics = validate(vec![
+        ShrubberyStep::SpawnRoot(SpawnRootStep::default()),
+        ShrubberyStep::Grow(GrowStep {
+            filter: Filter {
+                ignore_root: false,
+                ..Default::default()
+            },
+            ..Default::default()
+        }),
+    ]);
+    assert_eq!(diagnostics, vec![]);
+}
+
+#[test]
+fn reports_broken_grow_step() {
+    let diagnostics = validate(vec![
+        ShrubberyStep::SpawnRoot(SpawnRootStep::default()),
+        ShrubberyStep::Grow(GrowStep {
+            length: ValueOrRangeF32::Range(5.0, 2.0),
+            dir: BranchGrowthDirection::Attractor(AttractorSettings {
+                kill_distance: 10.0,
+                attract_distance: 5.0,
+                ..Default::default()
+            }),
+            voxel: DecorationSelector::Random(vec![]),
+            filter: Filter {
+                id: IdFilter::Target(7),
+                ..Default::default()
+            },
+            ..Default::default()
+        }),
+    ]);
+    assert!(has(&diagnostics, 1, "length", Severity::Warning));
+    assert!(has(&diagnostics, 1, "dir.kill_distance", Severity::Error));
+    assert!(has(&diagnostics, 1, "voxel", Severity::Error));
+    assert!(has(&diagnostics, 1, "filter.id", Severity::Error));
+    assert_eq!(diagnostics.len(), 4);
+}
+
+#[test]
+fn reports_unsorted_gradient() {
+    let step = |percent: f32| LeafGradientEntry {
+        percent,
+        voxel: VoxelMapping::default(),
+    };
+    let diagnostics = validate(vec![
+        ShrubberyStep::SpawnRoot(SpawnRootStep::default()),
+        ShrubberyStep::Grow(GrowStep {
+            voxel: DecorationSelector::Value(LeafDecoration::Gradient(LeafGradientSettings {
+                sampling_method: Default::default(),
+                modulation: None,
+                steps: vec![step(0.2), step(0.8), step(0.5)],
+            })),
+            ..Default::default()
+        }),
+    ]);
+    assert!(has(
+        &diagnostics,
+        1,
+        "voxel.steps[2].percent",
+        Severity::Error
+    ));
+}
+
+#[test]
+fn reports_unresolved_tags() {
+    let mut settings = ShrubberySettings {
+        build_steps: vec![
+            ShrubberyStep::SpawnRoot(SpawnRootStep {
+                tags: vec![BranchTag::new("root")],
+                ..Default::default()
+            }),
+            ShrubberyStep::Grow(GrowStep {
+                filter: Filter {
+                    ignore_root: false,
+                    id: IdFilter::Tag(BranchTag::new("root")),
+                    ..Default::default()
+                },
+                ..Default::default()
+            }),
+        ],
+    };
+    let diagnostics = settings.validate();
+    assert!(has(&diagnostics, 0, "tags[0]", Severity::Error));
+    assert!(has(&diagnostics, 1, "filter.id", Severity::Error));
+    assert_eq!(diagnostics.len(), 2);
+
+    settings.resolve_tags();
+    assert_eq!(settings.validate(), vec![]);
+}
+
+#[test]
+fn warns_on_attractors_that_cannot_pull() {
+    for weight in [0.0, -1.0, f32::NAN, f32::INFINITY] {
+        let diagnostics = validate(vec![ShrubberyStep::SpawnAttractors(SpawnAttractors {
+            chance: StepChance::Always,
+            location: SpawnAttractorLocation::Pos(Vec3::ZERO),
+            shape: AttractorShape::Sphere { radius: 4.0 },
+            attractor_spacing: AttractorSpacing::default(),
+            density: AttractorDensity::Uniform,
+            weight,
+            set: String::new(),
+            reject_inside_branches: false,
+        })]);
+        assert!(
+            has(&diagnostics, 0, "weight", Severity::Warning),
+            "{weight}: {diagnostics:#?}"
+        );
+    }
+}
+
+#[test]
+fn names_the_unknown_cleared_set() {
+    let diagnostics = validate(vec![ShrubberyStep::ClearAttractorSet("crown".to_string())]);
+    assert!(has(&diagnostics, 0, "set", Severity::Warning));
+}
+
+#[test]
+fn warns_on_forks_that_end_their_chain() {
+    for (count, warns) in [
+        (ValueOrRangeU32::Value(0), true),
+        (ValueOrRangeU32::Range(0, 3), true),
+        (ValueOrRangeU32::Range(1, 3), false),
+    ] {
+        let diagnostics = validate(vec![
+            ShrubberyStep::SpawnRoot(SpawnRootStep::default()),
+            ShrubberyStep::Grow(GrowStep {
+                spawn_method: BranchSpawnMethod::Fork(Fork {
+                    count,
+                    ..Default::default()
+                }),
+                filter: Filter {
+                    ignore_root: false,
+                    ..Default::default()
+                },
+                ..Default::default()
+            }),
+        ]);
+        assert_eq!(
+            has(&diagnostics, 1, "spawn_method.count", Severity::Warning),
+            warns,
+            "{count:?}: {diagnostics:#?}"
+        );
+    }
+}
@@ -0,0 +1,114 @@
+//! Round trips between the flat voxelize output and [`VoxelGrid`].
+
+use glam::{IVec3, UVec3, ivec3};
+use shrubbery_voxel::{prelude::*, voxel::VoxelId};
+
+fn sample_voxels() -> Vec<(IVec3, VoxelId)> {
+    vec![
+        (ivec3(-3, 0, 2), VoxelId(7)),
+        (ivec3(4, 5, -1), VoxelId(2)),
+        (ivec3(0, 0, 0), VoxelId(7)),
+        (ivec3(31, 32, 33), VoxelId(9)),
+    ]
+}
+
+fn sorted(mut voxels: Vec<(IVec3, VoxelId)>) -> Vec<(IVec3, VoxelId)> {
+    voxels.sort_by_key(|(pos, _)| (pos.x, pos.y, pos.z));
+    voxels
+}
+
+#[test]
+fn vec_round_trip() {
+    let voxels = sample_voxels();
+    let grid = VoxelGrid::from_voxels(&voxels).unwrap();
+
+    assert_eq!(grid.origin(), ivec3(-3, 0, -1));
+    assert_eq!(grid.size(), UVec3::new(35, 33, 35));
+    assert_eq!(grid.palette().len(), 3);
+    assert_eq!(grid.len(), voxels.len());
+    for (pos, voxel_id) in &voxels {
+        assert_eq!(grid.get(*pos), Some(*voxel_id));
+    }
+    assert_eq!(grid.get(ivec3(1, 1, 1)), None);
+    assert_eq!(grid.get(ivec3(100, 0, 0)), None);
+    assert_eq!(sorted(grid.to_vec()), sorted(voxels));
+}
+
+#[test]
+fn chunks_cover_every_voxel_once() {
+    let voxels = sample_voxels();
+    let grid = VoxelGrid::from_voxels(&voxels).unwrap();
+
+    let mut from_chunks = vec![];
+    for (coord, chunk) in grid.chunks(32) {
+        assert_eq!(chunk.origin(), coord * 32);
+        assert_eq!(chunk.size(), UVec3::splat(32));
+        assert!(!chunk.is_empty());
+        from_chunks.extend(chunk.iter());
+    }
+    assert_eq!(sorted(from_chunks), sorted(voxels));
+}
+
+#[test]
+fn oversized_grids_are_rejected() {
+    let corners = [
+        (ivec3(i32::MIN, 0, 0), VoxelId(1)),
+        (ivec3(i32::MAX, 0, 0), VoxelId(1)),
+    ];
+    assert!(matches!(
+        VoxelGrid::from_voxels(&corners),
+        Err(VoxelGridError::TooLarge { .. })
+    ));
+    assert_eq!(
+        VoxelGrid::new(IVec3::ZERO, UVec3::MAX),
+        Err(VoxelGridError::TooLarge { size: UVec3::MAX })
+    );
+    // fits in usize, but not in a sane allocation
+    let size = UVec3::splat(1024);
+    assert_eq!(
+        VoxelGrid::new(IVec3::ZERO, size),
+        Err(VoxelGridError::TooLarge { size })
+    );
+    let spread = [(IVec3::ZERO, VoxelId(1)), (IVec3::splat(1023), VoxelId(1))];
+    assert!(matches!(
+        VoxelGrid::from_voxels(&spread),
+        Err(VoxelGridError::TooLarge { .. })
+    ));
+    assert!(VoxelGrid::new(IVec3::ZERO, UVec3::splat(256)).is_ok());
+}
+
+#[test]
+fn chunk_size_is_clamped() {
+    let grid = VoxelGrid::from_voxels(&sample_voxels()).unwrap();
+    for (_, chunk) in grid.chunks(u32::MAX) {
+        assert_eq!(chunk.size(), UVec3::splat(256));
+    }
+    for (_, chunk) in grid.chunks(0) {
+        assert_eq!(chunk.size(), UVec3::ONE);
+    }
+}
+
+/// Needs `ron`.
+#[cfg(feature = "serde")]
+#[test]
+fn deserialize_validates_cells() {
+    let grid = VoxelGrid::from_voxels(&sample_voxels()).unwrap();
+    let round_trip: VoxelGrid = ron::from_str(&ron::to_string(&grid).unwrap()).unwrap();
+    assert_eq!(round_trip, grid);
+
+    let short = "(origin: (0, 0, 0), size: (2, 1, 1), palette: [(1)], cells: [1])";
+    let err = ron::from_str::<VoxelGrid>(short).unwrap_err().to_string();
+    assert!(err.contains("grid of 2 cells stores 1"), "{err}");
+
+    let bad_palette = "(origin: (0, 0, 0), size: (2, 1, 1), palette: [(1)], cells: [1, 2])";
+    let err = ron::from_str::<VoxelGrid>(bad_palette)
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("cell 2 points past the palette of 1"), "{err}");
+
+    let too_large = "(origin: (0, 0, 0), size: (1024, 1024, 1024), palette: [], cells: [])";
+    let err = ron::from_str::<VoxelGrid>(too_large)
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("has more than"), "{err}");
+}